@@ -0,0 +1,118 @@
+use crate::evaluation::{nega_max_with_context, BoardState, NegaMaxOptions, SearchContext};
+use crate::pruning::PruningParams;
+use crate::transposition::TranspositionTable;
+use chess::{Board, ChessMove};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_BENCH_DEPTH: i8 = 7;
+// every bench position gets a fresh table of this size, so runs don't depend on each other.
+const BENCH_HASH_MB: usize = 16;
+
+/// Positions from Win At Chess that the engine solves, with the move that solves them.
+/// A change that makes the engine miss one of them is most likely a bug.
+pub const TACTICS: [(&str, &str); 9] = [
+    (
+        "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1",
+        "g3g6",
+    ),
+    (
+        "5rk1/1ppb3p/p1pb4/6q1/3P1p1r/2P1R2P/PP1BQ1P1/5RKN w - - 0 1",
+        "e3g3",
+    ),
+    (
+        "r1bq2rk/pp3pbp/2p1p1pQ/7P/3P4/2PB1N2/PP3PPR/2KR4 w - - 0 1",
+        "h6h7",
+    ),
+    ("5k2/6pp/p1qN4/1p1p4/3P4/2PKP2Q/PP3r2/3R4 b - - 0 1", "c6c4"),
+    ("7k/p7/1R5K/6r1/6p1/6P1/8/8 w - - 0 1", "b6b7"),
+    (
+        "rnbqkb1r/pppp1ppp/8/4P3/6n1/7P/PPPNPPP1/R1BQKBNR b KQkq - 0 1",
+        "g4e3",
+    ),
+    (
+        "r4q1k/p2bR1rp/2p2Q1N/5p2/5p2/2P5/PP3PPP/R5K1 w - - 0 1",
+        "e7f7",
+    ),
+    (
+        "3q1rk1/p4pp1/2pb3p/3p4/6Pr/1PNQ4/P1PB1PP1/4RRK1 b - - 0 1",
+        "d6h2",
+    ),
+    (
+        "2br2k1/2q3rn/p2NppQ1/2p1P3/Pp5R/4P3/1P3PPP/3R2K1 w - - 0 1",
+        "h4h7",
+    ),
+];
+
+/// Quiet positions from the opening, middlegame and endgame, searched along with the tactics.
+pub const QUIET_POSITIONS: [&str; 4] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP3PPP/R2QKB1R w KQ - 0 1",
+    "8/5pk1/6p1/3P4/1p3P2/1P4P1/6K1/8 w - - 0 1",
+];
+
+/// What a bench run found and how much work it took.
+#[derive(Clone, Debug, Default)]
+pub struct BenchResult {
+    pub nodes: u64,
+    pub elapsed: Duration,
+    pub solved: usize,
+}
+
+impl BenchResult {
+    pub fn nps(&self) -> u64 {
+        return self.nodes * 1000 / (self.elapsed.as_millis() as u64).max(1);
+    }
+}
+
+/// Searches every bench position to `depth` with the given pruning and adds up the nodes.
+pub fn run(depth: i8, pruning: PruningParams) -> BenchResult {
+    let start = Instant::now();
+    let mut result = BenchResult::default();
+    for (fen, best) in TACTICS {
+        let (nodes, best_move) = search(fen, depth, pruning);
+        result.nodes += nodes;
+        if best_move == ChessMove::from_str(best).ok() {
+            result.solved += 1;
+        }
+    }
+    for fen in QUIET_POSITIONS {
+        result.nodes += search(fen, depth, pruning).0;
+    }
+    result.elapsed = start.elapsed();
+    return result;
+}
+
+/// Iterative deepening up to `depth` from `fen`, returns the nodes searched over all the
+/// iterations and the move the last one settled on.
+pub fn search(fen: &str, depth: i8, pruning: PruningParams) -> (u64, Option<ChessMove>) {
+    let board = Board::from_str(fen).expect("bench positions are valid");
+    let opts = NegaMaxOptions::new()
+        .tt(Arc::new(TranspositionTable::new(BENCH_HASH_MB)))
+        .pruning(pruning);
+    let mut ctx = SearchContext::new();
+    let mut nodes = 0;
+    let mut best_move = None;
+    for d in 1..=depth {
+        ctx.orderer.age();
+        let result = nega_max_with_context(BoardState::from_board(board), opts.depth(d), &mut ctx);
+        nodes += result.nodes;
+        best_move = result.pv.first().copied().or(best_move);
+    }
+    return (nodes, best_move);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tactics_are_solved_with_pruning() {
+        for (fen, best) in TACTICS {
+            let (_, best_move) = search(fen, 6, PruningParams::new());
+            assert_eq!(best_move, ChessMove::from_str(best).ok(), "{}", fen);
+        }
+    }
+}
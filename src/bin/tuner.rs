@@ -0,0 +1,126 @@
+#![allow(clippy::needless_return)]
+
+use rust_engine::eval_params::EvalParams;
+use rust_engine::tuner::{load_epd, load_pgn, mean_squared_error, Tuner, TuningPosition};
+use std::path::{Path, PathBuf};
+
+const USAGE: &str =
+    "usage: tuner [--out FILE] [--init FILE] [--k K] [--step N] [--iterations N] [CORPUS...]
+  CORPUS   .epd or .pgn files or directories of them (default ./tmp/games)
+  --out    where to write the tuned parameters (default tuned.params), loadable with EvalFile
+  --init   parameter file to start from instead of the built-in values
+  --k      fixed sigmoid scaling constant, fitted to the starting parameters if left out";
+
+struct TunerOptions {
+    corpus: Vec<PathBuf>,
+    out: PathBuf,
+    init: Option<PathBuf>,
+    k: Option<f64>,
+    step: i32,
+    iterations: usize,
+}
+
+impl TunerOptions {
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut opts = Self {
+            corpus: Vec::new(),
+            out: PathBuf::from("tuned.params"),
+            init: None,
+            k: None,
+            step: 1,
+            iterations: 100,
+        };
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let mut value = || iter.next().ok_or(format!("{} needs a value", arg));
+            match arg.as_str() {
+                "--out" => opts.out = PathBuf::from(value()?),
+                "--init" => opts.init = Some(PathBuf::from(value()?)),
+                "--k" => opts.k = Some(value()?.parse().map_err(|_| "invalid --k")?),
+                "--step" => opts.step = value()?.parse().map_err(|_| "invalid --step")?,
+                "--iterations" => {
+                    opts.iterations = value()?.parse().map_err(|_| "invalid --iterations")?
+                }
+                "-h" | "--help" => return Err(USAGE.to_string()),
+                _ if arg.starts_with("--") => {
+                    return Err(format!("unknown option {}\n{}", arg, USAGE))
+                }
+                _ => opts.corpus.push(PathBuf::from(arg)),
+            }
+        }
+        if opts.corpus.is_empty() {
+            opts.corpus.push(PathBuf::from("./tmp/games"));
+        }
+        return Ok(opts);
+    }
+}
+
+fn load_corpus(path: &Path, positions: &mut Vec<TuningPosition>) -> std::io::Result<()> {
+    if path.is_dir() {
+        for entry in std::fs::read_dir(path)? {
+            load_corpus(&entry?.path(), positions)?;
+        }
+        return Ok(());
+    }
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("epd") => positions.extend(load_epd(&std::fs::read_to_string(path)?)),
+        Some("pgn") => positions.extend(load_pgn(&std::fs::read_to_string(path)?)),
+        _ => (),
+    }
+    return Ok(());
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let opts = match TunerOptions::from_args(&args) {
+        Ok(opts) => opts,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+
+    let mut positions = Vec::new();
+    for path in &opts.corpus {
+        if let Err(e) = load_corpus(path, &mut positions) {
+            eprintln!("could not read {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    }
+    if positions.is_empty() {
+        eprintln!("no positions with results found\n{}", USAGE);
+        std::process::exit(1);
+    }
+
+    let params = match &opts.init {
+        Some(path) => match EvalParams::load(path) {
+            Ok(params) => params,
+            Err(e) => {
+                eprintln!("could not load {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        },
+        None => EvalParams::default(),
+    };
+
+    println!("tuning on {} positions", positions.len());
+    let mut tuner = Tuner::new(positions)
+        .step(opts.step)
+        .max_iterations(opts.iterations);
+    let k = opts.k.unwrap_or_else(|| tuner.fit_k(&params));
+    tuner = tuner.k(k);
+    println!(
+        "k = {:.2}, starting error {:.6}",
+        k,
+        mean_squared_error(tuner.positions(), &params, k)
+    );
+
+    tuner.run(&params, |iteration, error, tuned| {
+        println!("iteration {} error {:.6}", iteration, error);
+        // write after every pass so a long run can be stopped at any time.
+        if let Err(e) = tuned.save(&opts.out) {
+            eprintln!("could not write {}: {}", opts.out.display(), e);
+        }
+    });
+    println!("wrote {}", opts.out.display());
+}
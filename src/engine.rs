@@ -1,33 +1,186 @@
-use std::collections::HashMap;
-
 use crate::{
-    evaluation::{nega_max, task_must_stop, BoardState, NegaMaxOptions, NegaMaxResult, MIN_SCORE},
+    eval_params::EvalParams,
+    evaluation::{
+        is_mate_score, mate_distance, nega_max_with_context, BoardState, NegaMaxOptions,
+        NegaMaxResult, SearchContext, MIN_SCORE,
+    },
+    evaluator::{Evaluator, MaterialEvaluator, PstEvaluator},
+    mobility::EvalTerms,
+    pruning::{
+        PruningParams, DEFAULT_FUTILITY_MARGIN, DEFAULT_LMP_BASE, DEFAULT_REVERSE_FUTILITY_MARGIN,
+    },
+    reductions::{ReductionTable, DEFAULT_LMR_BASE, DEFAULT_LMR_DIVISOR},
+    time_manager::can_start_iteration,
+    transposition::{Bound, TranspositionTable, DEFAULT_HASH_MB},
     uci::UCIEngineOptions,
 };
-use chess::{Board, ChessMove, MoveGen};
-use rayon::prelude::*;
+use chess::{Board, ChessMove};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// half the width of the first aspiration window, doubled every time the search falls outside it.
+const ASPIRATION_WINDOW: i32 = 25;
+// shallow iterations are cheap and their scores swing too much to be worth guessing around.
+const ASPIRATION_MIN_DEPTH: i8 = 4;
+// one search thread unless the gui asks for more.
+pub const DEFAULT_THREADS: usize = 1;
+pub const MAX_THREADS: usize = 256;
+// how many of the best root moves are reported with their own score and line.
+pub const DEFAULT_MULTI_PV: usize = 1;
+pub const MAX_MULTI_PV: usize = 256;
+// the evaluations the "Evaluator" option can choose from, the first one is the default.
+pub const EVALUATORS: [&str; 2] = ["pst", "material"];
+
+/// Builds the engine the uci options describe. The evaluator is chosen at runtime, so that
+/// two instances of the same binary can play each other with different evaluations.
+pub fn get_engine(opts: UCIEngineOptions) -> Box<dyn Engine + Send> {
+    let hash_mb = opts
+        .get("Hash")
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_HASH_MB);
+    if opts.get("Evaluator").map(String::as_str) == Some(EVALUATORS[1]) {
+        let engine = ChessEngine::with_evaluator(hash_mb, MaterialEvaluator);
+        return Box::new(search_options(engine, &opts));
+    }
+    let terms = EvalTerms {
+        mobility: check_option(&opts, "Mobility"),
+        bishop_pair: check_option(&opts, "Bishop Pair"),
+        rook_open_file: check_option(&opts, "Rook Open File"),
+        rook_seventh: check_option(&opts, "Rook Seventh"),
+    };
+    let mut eval = PstEvaluator::with_terms(terms);
+    if let Some(path) = opts
+        .get("EvalFile")
+        .filter(|p| !p.is_empty() && *p != "<empty>")
+    {
+        match EvalParams::load(path) {
+            Ok(params) => eval = eval.params(params),
+            Err(e) => println!("info string could not load {}: {}", path, e),
+        }
+    }
+    let engine = ChessEngine::with_evaluator(hash_mb, eval);
+    return Box::new(search_options(engine, &opts));
+}
+
+// applies the options that tune the search rather than the evaluation.
+fn search_options<E: Evaluator>(engine: ChessEngine<E>, opts: &UCIEngineOptions) -> ChessEngine<E> {
+    let spin = |name: &str, default: u32| {
+        return opts
+            .get(name)
+            .and_then(|v| v.parse::<u32>().ok())
+            .unwrap_or(default);
+    };
+    let lmr = ReductionTable::new(
+        spin("LMR Base", DEFAULT_LMR_BASE),
+        spin("LMR Divisor", DEFAULT_LMR_DIVISOR),
+    );
+    let pruning = PruningParams {
+        futility_margin: spin("Futility Margin", DEFAULT_FUTILITY_MARGIN as u32) as i32,
+        reverse_futility_margin: spin(
+            "Reverse Futility Margin",
+            DEFAULT_REVERSE_FUTILITY_MARGIN as u32,
+        ) as i32,
+        lmp_base: spin("LMP Base", DEFAULT_LMP_BASE as u32) as usize,
+    };
+    let threads = opts
+        .get("Threads")
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_THREADS);
+    let multi_pv = opts
+        .get("MultiPV")
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_MULTI_PV);
+    return engine
+        .lmr(lmr)
+        .pruning(pruning)
+        .threads(threads)
+        .multi_pv(multi_pv);
+}
 
-pub fn get_engine(_: UCIEngineOptions) -> ChessEngine {
-    return ChessEngine::new();
+// evaluation terms are on unless the gui explicitly turned them off.
+fn check_option(opts: &UCIEngineOptions, name: &str) -> bool {
+    return opts.get(name).map(|v| v != "false").unwrap_or(true);
 }
 
 pub trait Engine {
     fn next_move(&self, board: &Board, opts: NegaMaxOptions) -> Option<ChessMove>;
 }
 
-pub struct ChessEngine {
+impl<T: Engine + ?Sized> Engine for Box<T> {
+    fn next_move(&self, board: &Board, opts: NegaMaxOptions) -> Option<ChessMove> {
+        return (**self).next_move(board, opts);
+    }
+}
+
+pub struct ChessEngine<E: Evaluator = PstEvaluator> {
     debug: bool,
+    tt: Arc<TranspositionTable>,
+    eval: Arc<E>,
+    lmr: Arc<ReductionTable>,
+    pruning: PruningParams,
+    threads: usize,
+    multi_pv: usize,
 }
 
 impl ChessEngine {
     pub fn new() -> Self {
+        return Self::with_hash(DEFAULT_HASH_MB);
+    }
+
+    /// Create an engine whose transposition table uses roughly `hash_mb` megabytes.
+    pub fn with_hash(hash_mb: usize) -> Self {
+        return Self::with_evaluator(hash_mb, PstEvaluator::new());
+    }
+}
+
+impl<E: Evaluator> ChessEngine<E> {
+    /// Create an engine that scores positions with `eval`.
+    pub fn with_evaluator(hash_mb: usize, eval: E) -> Self {
         //let pgn_encoder = PgnEncoder::new(game.current_position(), None);
         let debug = false;
-        return Self { debug };
+        let tt = Arc::new(TranspositionTable::new(hash_mb));
+        let eval = Arc::new(eval);
+        let lmr = Arc::new(ReductionTable::default());
+        return Self {
+            debug,
+            tt,
+            eval,
+            lmr,
+            pruning: PruningParams::default(),
+            threads: DEFAULT_THREADS,
+            multi_pv: DEFAULT_MULTI_PV,
+        };
     }
 
-    fn get_curr_state(board: &Board) -> BoardState {
-        return BoardState::from_board(*board);
+    /// Search with `threads` threads in total, the main one and `threads - 1` helpers.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.clamp(1, MAX_THREADS);
+        return self;
+    }
+
+    /// Report the best `multi_pv` root moves, each with its own score and line. The move
+    /// played is still the best one.
+    pub fn multi_pv(mut self, multi_pv: usize) -> Self {
+        self.multi_pv = multi_pv.clamp(1, MAX_MULTI_PV);
+        return self;
+    }
+
+    /// Use `lmr` to decide how much late moves are reduced.
+    pub fn lmr(mut self, lmr: ReductionTable) -> Self {
+        self.lmr = Arc::new(lmr);
+        return self;
+    }
+
+    /// Use `pruning` to decide which moves near the horizon aren't worth searching.
+    pub fn pruning(mut self, pruning: PruningParams) -> Self {
+        self.pruning = pruning;
+        return self;
+    }
+
+    fn get_curr_state(&self, board: &Board, opts: &NegaMaxOptions) -> BoardState<E> {
+        let clock = opts.get_history().halfmove_clock();
+        return BoardState::with_evaluator(*board, self.eval.clone()).halfmove_clock(clock);
     }
 
     pub fn set_debug(&mut self, b: bool) {
@@ -35,80 +188,373 @@ impl ChessEngine {
     }
 }
 
-// impl Engine for ChessEngine {
-//     fn next_move(&self, board: &Board, opts: NegaMaxOptions) -> Option<ChessMove> {
-//         let ideal_moves = MoveGen::new_legal(&board)
-//             .par_bridge()
-//             .map(|m| {
-//                 let mut state = self.get_curr_state(&board);
-//                 state.apply_move(m);
-//                 let score = -nega_max(state, opts.clone());
-//                 return (score, m);
-//             })
-//             .collect();
-//         return self.aggregate_results(ideal_moves);
-//     }
-// }
+impl Default for ChessEngine {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
 
-impl Engine for ChessEngine {
+impl<E: Evaluator> Engine for ChessEngine<E> {
+    /// Lazy SMP: every thread searches the whole tree from the root and they only share the
+    /// transposition table, which is enough for the helpers to fill it with results the main
+    /// thread then finds ready. The main thread alone decides when to stop and what to play.
     fn next_move(&self, board: &Board, opts: NegaMaxOptions) -> Option<ChessMove> {
-        // Collect legal moves once.
-        let legal_moves: Vec<ChessMove> = MoveGen::new_legal(board).collect();
-        let mut best_move = None;
-        // Determine maximum depth from the options.
-        let max_depth = opts.get_depth();
-        let global_time = opts.get_mtime();
-        let signal = opts.get_signal();
-        // Iterative deepening loop in the main thread:
-        let state = Self::get_curr_state(board);
-        for current_depth in 1..=max_depth {
-            // Dispatch parallel search for each legal move:
-            let results: Vec<(NegaMaxResult, ChessMove)> = legal_moves
-                .par_iter()
-                .map(|m| search_move(&state, opts.depth(current_depth), m))
-                .collect();
-
-            aggregate_results(results).map(|m| {
-                println!("info depth {}", current_depth);
-                best_move = Some(m);
-            });
-            // Check overall time and break if reached.
-            if task_must_stop(&global_time, &signal) {
-                break;
-            }
+        let root_moves = opts.root_moves(board);
+        if root_moves.is_empty() {
+            return None;
         }
+        // Every thread shares the engine's transposition table.
+        let opts = opts
+            .tt(self.tt.clone())
+            .lmr(self.lmr.clone())
+            .pruning(self.pruning);
+        let state = self.get_curr_state(board, &opts);
+        // a node budget is counted per thread, helpers would search past it and make the
+        // result depend on how the threads happened to be scheduled.
+        let threads = if opts.get_nodes().is_some() {
+            1
+        } else {
+            self.threads
+        };
+        // the helpers don't watch the clock, they run until the main thread is done.
+        let helpers_stop = Arc::new(AtomicBool::new(false));
+        let helper_nodes = AtomicU64::new(0);
+        return std::thread::scope(|scope| {
+            for id in 1..threads {
+                let state = state.clone();
+                let opts = opts.signal(helpers_stop.clone());
+                let helper_nodes = &helper_nodes;
+                scope.spawn(move || helper_search(id, state, opts, helper_nodes));
+            }
+            let mut ctx = SearchContext::new();
+            let (best_move, _) =
+                main_search(&state, &opts, self.multi_pv, &mut ctx, &helper_nodes);
+            helpers_stop.store(true, Ordering::Relaxed);
+            // if we were stopped before the first iteration finished, any move beats none.
+            return best_move.or(root_moves.first().copied());
+        });
+    }
+}
 
-        best_move
+/// Iterative deepening with aspiration windows, reporting every iteration to the gui. With
+/// `multi_pv` above one, every iteration searches the root again for each extra line, leaving
+/// out the moves of the lines already found, and reports the lines best first once they are
+/// all searched. Killers and history in `ctx` carry over from one iteration to the next.
+/// Returns the move to play and the lines of the last iteration that got anywhere.
+fn main_search<E: Evaluator>(
+    state: &BoardState<E>,
+    opts: &NegaMaxOptions,
+    multi_pv: usize,
+    ctx: &mut SearchContext,
+    helper_nodes: &AtomicU64,
+) -> (Option<ChessMove>, Vec<NegaMaxResult>) {
+    let mut best_move = None;
+    let mut best_lines = Vec::new();
+    let max_depth = opts.get_depth();
+    let global_time = opts.get_mtime();
+    let soft_time = opts.get_soft_mtime();
+    let start = Instant::now();
+    let mut total_nodes = 0;
+    let lines = multi_pv.min(opts.root_moves(&state.board).len()).max(1);
+    let mut last_scores = vec![None; lines];
+    for current_depth in 1..=max_depth {
+        let iteration_start = Instant::now();
+        ctx.orderer.age();
+        ctx.root_excluded.clear();
+        let mut finished: Vec<NegaMaxResult> = Vec::with_capacity(lines);
+        for (line, last_score) in last_scores.iter().enumerate() {
+            // Aspiration windows: expect the score to stay close to the last iteration's and
+            // search a narrow window around it, widening it whenever the score falls outside.
+            let mut delta = ASPIRATION_WINDOW;
+            let (mut alpha, mut beta) = match *last_score {
+                Some(score) if current_depth >= ASPIRATION_MIN_DEPTH && !is_mate_score(score) => (
+                    (score - delta).max(MIN_SCORE),
+                    (score + delta).min(-MIN_SCORE),
+                ),
+                _ => (MIN_SCORE, -MIN_SCORE),
+            };
+            let mut line_result = None;
+            loop {
+                let window = opts.depth(current_depth).window(alpha, beta);
+                let result = nega_max_with_context(state.clone(), window, ctx);
+                total_nodes += result.nodes;
+                if !result.is_complete {
+                    break;
+                }
+                let bound = if result.score <= alpha && alpha > MIN_SCORE {
+                    Bound::Upper
+                } else if result.score >= beta && beta < -MIN_SCORE {
+                    Bound::Lower
+                } else {
+                    Bound::Exact
+                };
+                // with several lines only the finished ones are reported, once they are sorted.
+                if lines == 1 || bound != Bound::Exact {
+                    let nodes = total_nodes + helper_nodes.load(Ordering::Relaxed);
+                    print_info(
+                        current_depth,
+                        line + 1,
+                        &result,
+                        bound,
+                        nodes,
+                        start.elapsed(),
+                    );
+                }
+                // a mate is far outside any window we'd widen to, go straight to the limit.
+                if is_mate_score(result.score) {
+                    delta = -MIN_SCORE;
+                }
+                match bound {
+                    Bound::Upper => alpha = (alpha - delta).max(MIN_SCORE),
+                    Bound::Lower => {
+                        // a move that beats the window is at least as good as anything we had.
+                        if line == 0 {
+                            best_move = result.pv.first().copied().or(best_move);
+                        }
+                        beta = (beta + delta).min(-MIN_SCORE);
+                    }
+                    Bound::Exact => {
+                        line_result = Some(result);
+                        break;
+                    }
+                }
+                delta *= 2;
+                if opts.must_stop(ctx) {
+                    break;
+                }
+            }
+            // the next line is the best among the moves not reported yet.
+            match line_result {
+                Some(result) if !result.pv.is_empty() => {
+                    ctx.root_excluded.push(result.pv[0]);
+                    finished.push(result);
+                }
+                _ => break,
+            }
+        }
+        // the lines are searched one after the other, so a later one can come out ahead.
+        finished.sort_by_key(|result| -result.score);
+        if lines > 1 {
+            let nodes = total_nodes + helper_nodes.load(Ordering::Relaxed);
+            for (line, result) in finished.iter().enumerate() {
+                print_info(
+                    current_depth,
+                    line + 1,
+                    result,
+                    Bound::Exact,
+                    nodes,
+                    start.elapsed(),
+                );
+            }
+        }
+        for (last_score, result) in last_scores.iter_mut().zip(&finished) {
+            *last_score = Some(result.score);
+        }
+        if let Some(top) = finished.first() {
+            best_move = top.pv.first().copied();
+            best_lines = finished;
+        }
+        // Check overall time and break if reached.
+        if opts.must_stop(ctx) {
+            break;
+        }
+        // go mate: once a short enough mate is proven there is nothing left to look for.
+        let mate_found = last_scores[0]
+            .and_then(mate_distance)
+            .zip(opts.get_mate())
+            .is_some_and(|(moves, limit)| moves > 0 && moves as u32 <= limit);
+        if mate_found {
+            break;
+        }
+        // Don't start an iteration we most likely can't finish.
+        let now = Instant::now();
+        if !can_start_iteration(now, now - iteration_start, soft_time, global_time) {
+            break;
+        }
     }
+    return (best_move, best_lines);
 }
 
-fn search_move(
-    state: &BoardState,
+/// Iterative deepening for a helper thread. Half of the helpers start one ply deeper than the
+/// main thread so that they don't all search the same depth at the same time. Two starting
+/// depths are deliberate: a helper further ahead fills the table with results for iterations
+/// the main thread doesn't reach in time to use them.
+fn helper_search<E: Evaluator>(
+    id: usize,
+    state: BoardState<E>,
     opts: NegaMaxOptions,
-    m: &ChessMove,
-) -> (NegaMaxResult, ChessMove) {
-    let score = -nega_max(state.apply_move(m), opts);
-    (score, *m)
+    nodes: &AtomicU64,
+) {
+    let mut ctx = SearchContext::new();
+    let first_depth = 1 + (id % 2) as i8;
+    for depth in first_depth..=opts.get_depth() {
+        ctx.orderer.age();
+        let result = nega_max_with_context(state.clone(), opts.depth(depth), &mut ctx);
+        nodes.fetch_add(result.nodes, Ordering::Relaxed);
+        if !result.is_complete {
+            return;
+        }
+    }
 }
 
-fn aggregate_results(results: Vec<(NegaMaxResult, ChessMove)>) -> Option<ChessMove> {
-    if results.len() < 1 {
-        return None;
+/// Formats a score for uci, mate scores are reported as the number of moves until mate.
+pub fn uci_score(score: i32) -> String {
+    if let Some(moves) = mate_distance(score) {
+        return format!("mate {}", moves);
     }
-    let mut max_score = MIN_SCORE;
-    let mut total_nodes = 0;
-    let mut best_move = None;
-    let mut is_incomplete = false;
-    for (result, m) in results {
-        if !result.is_complete {
-            is_incomplete = true;
+    return format!("cp {}", score);
+}
+
+fn print_info(
+    depth: i8,
+    multi_pv: usize,
+    result: &NegaMaxResult,
+    bound: Bound,
+    nodes: u64,
+    elapsed: Duration,
+) {
+    println!("{}", info_line(depth, multi_pv, result, bound, nodes, elapsed));
+}
+
+fn info_line(
+    depth: i8,
+    multi_pv: usize,
+    result: &NegaMaxResult,
+    bound: Bound,
+    nodes: u64,
+    elapsed: Duration,
+) -> String {
+    let ms = elapsed.as_millis() as u64;
+    let nps = nodes * 1000 / ms.max(1);
+    let bound = match bound {
+        Bound::Exact => "",
+        Bound::Lower => " lowerbound",
+        Bound::Upper => " upperbound",
+    };
+    let mut line = format!(
+        "info depth {} seldepth {} multipv {} score {}{} nodes {} nps {} time {}",
+        depth,
+        result.seldepth,
+        multi_pv,
+        uci_score(result.score),
+        bound,
+        nodes,
+        nps,
+        ms,
+    );
+    // a fail low has no move to show, and a bare "pv" token confuses some guis.
+    if !result.pv.is_empty() {
+        let pv: Vec<String> = result.pv.iter().map(|m| m.to_string()).collect();
+        line.push_str(" pv ");
+        line.push_str(&pv.join(" "));
+    }
+    return line;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn helper_threads_agree_on_the_move() {
+        let board = Board::from_str("r6k/4Rppp/8/8/8/8/8/4R2K w - - 0 1").unwrap();
+        let mate = ChessMove::from_str("e7e8").unwrap();
+        for threads in [1, 4] {
+            let engine = ChessEngine::with_hash(1).threads(threads);
+            let m = engine.next_move(&board, NegaMaxOptions::new().depth(4));
+            assert_eq!(m, Some(mate));
         }
-        total_nodes += result.nodes;
-        if result.score > max_score {
-            max_score = result.score;
-            best_move = Some(m);
+    }
+
+    #[test]
+    fn evaluator_option() {
+        let board = Board::from_str("r6k/4Rppp/8/8/8/8/8/4R2K w - - 0 1").unwrap();
+        let mate = ChessMove::from_str("e7e8").unwrap();
+        for name in EVALUATORS {
+            let opts = UCIEngineOptions::from([
+                ("Evaluator".to_string(), name.to_string()),
+                ("Hash".to_string(), "1".to_string()),
+            ]);
+            let m = get_engine(opts).next_move(&board, NegaMaxOptions::new().depth(4));
+            assert_eq!(m, Some(mate), "{}", name);
         }
     }
-    println!("info nodes {}", total_nodes);
-    return if is_incomplete { None } else { best_move };
+
+    #[test]
+    fn multi_pv_still_plays_the_best_move() {
+        let board = Board::from_str("r6k/4Rppp/8/8/8/8/8/4R2K w - - 0 1").unwrap();
+        let engine = ChessEngine::with_hash(1).multi_pv(3);
+        let m = engine.next_move(&board, NegaMaxOptions::new().depth(4));
+        assert_eq!(m, Some(ChessMove::from_str("e7e8").unwrap()));
+    }
+
+    #[test]
+    fn multi_pv_lines_are_reported_best_first() {
+        let board = Board::from_str("r6k/4Rppp/8/8/8/8/8/4R2K w - - 0 1").unwrap();
+        let engine = ChessEngine::with_hash(1);
+        let state = engine.get_curr_state(&board, &NegaMaxOptions::new());
+        let opts = NegaMaxOptions::new().depth(4);
+        let mut ctx = SearchContext::new();
+        let (m, lines) = main_search(&state, &opts, 3, &mut ctx, &AtomicU64::new(0));
+        assert_eq!(lines.len(), 3);
+        assert!(lines.windows(2).all(|pair| pair[0].score >= pair[1].score));
+        assert_eq!(m, lines[0].pv.first().copied());
+        assert_eq!(m, ChessMove::from_str("e7e8").ok());
+    }
+
+    #[test]
+    fn search_limits() {
+        let board = Board::from_str("r6k/4Rppp/8/8/8/8/8/4R2K w - - 0 1").unwrap();
+        let engine = ChessEngine::with_hash(1);
+        // a mate in two, without a depth limit only finding it ends the search.
+        let state = engine.get_curr_state(&board, &NegaMaxOptions::new());
+        let search = |opts: NegaMaxOptions| {
+            let mut ctx = SearchContext::new();
+            let (m, _) = main_search(&state, &opts, 1, &mut ctx, &AtomicU64::new(0));
+            return (m, ctx.nodes());
+        };
+        let (m, mate_nodes) = search(NegaMaxOptions::new().mate(2));
+        assert_eq!(m, ChessMove::from_str("e7e8").ok());
+        // a mate in one isn't there, so that search goes on to the depth limit.
+        let (_, deep_nodes) = search(NegaMaxOptions::new().depth(8).mate(1));
+        assert!(mate_nodes * 10 < deep_nodes);
+
+        let king = ChessMove::from_str("h1g1").unwrap();
+        let m = engine.next_move(
+            &board,
+            NegaMaxOptions::new().depth(3).search_moves(vec![king]),
+        );
+        assert_eq!(m, Some(king));
+
+        // a node budget makes the search the same on any machine.
+        let opts = NegaMaxOptions::new().nodes(20_000);
+        let first = ChessEngine::with_hash(1).next_move(&Board::default(), opts.clone());
+        let second = ChessEngine::with_hash(1).next_move(&Board::default(), opts.clone());
+        assert!(first.is_some());
+        assert_eq!(first, second);
+        // helper threads would each search their own budget.
+        let threaded = ChessEngine::with_hash(1).threads(4);
+        assert_eq!(threaded.next_move(&Board::default(), opts), first);
+    }
+
+    #[test]
+    fn node_budget_covers_the_whole_search() {
+        let state = BoardState::from_board(Board::default());
+        let opts = NegaMaxOptions::new().nodes(20_000);
+        let mut ctx = SearchContext::new();
+        main_search(&state, &opts, 1, &mut ctx, &AtomicU64::new(0));
+        assert!(ctx.nodes() >= 20_000 && ctx.nodes() < 21_000);
+    }
+
+    #[test]
+    fn info_line_leaves_out_an_empty_pv() {
+        let mut result = NegaMaxResult::new(-40);
+        let elapsed = Duration::from_millis(10);
+        let line = info_line(5, 1, &result, Bound::Upper, 100, elapsed);
+        assert!(line.ends_with("score cp -40 upperbound nodes 100 nps 10000 time 10"));
+        result.pv = vec![ChessMove::from_str("e2e4").unwrap()];
+        let line = info_line(5, 1, &result, Bound::Exact, 100, elapsed);
+        assert!(line.ends_with("time 10 pv e2e4"));
+    }
 }
@@ -0,0 +1,267 @@
+use crate::piece_table::{
+    piece_value, table_index, PieceTable, Score, BISHOP_TABLE, BISHOP_TABLE_END, KING_TABLE_END,
+    KING_TABLE_MID, KNIGHT_TABLE, KNIGHT_TABLE_END, PAWN_TABLE, PAWN_TABLE_END, QUEEN_TABLE,
+    QUEEN_TABLE_END, ROOK_TABLE, ROOK_TABLE_END,
+};
+use chess::{Color, File, Piece, Rank, ALL_PIECES, NUM_PIECES};
+use std::fmt;
+use std::path::Path;
+
+const PIECE_NAMES: [&str; NUM_PIECES] = ["pawn", "knight", "bishop", "rook", "queen", "king"];
+
+/// Piece values and piece-square tables that can be loaded at runtime. The defaults are the
+/// constants in `piece_table`.
+///
+/// The file format is one `name = values` entry per parameter, values are separated by commas
+/// or whitespace and may continue on the following lines. `#` starts a comment. The names are
+/// `<piece>_value`, `<piece>_mg` and `<piece>_eg` (64 values each, a8 to h1 from white's point
+/// of view), anything left out keeps its default.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EvalParams {
+    pub piece_values: [i32; NUM_PIECES],
+    pub mg_tables: [[i32; 64]; NUM_PIECES],
+    pub eg_tables: [[i32; 64]; NUM_PIECES],
+}
+
+#[derive(Debug)]
+pub enum ParamsError {
+    Io(std::io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for ParamsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamsError::Io(e) => write!(f, "{}", e),
+            ParamsError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for ParamsError {}
+
+impl From<std::io::Error> for ParamsError {
+    fn from(e: std::io::Error) -> Self {
+        return ParamsError::Io(e);
+    }
+}
+
+impl EvalParams {
+    pub fn new() -> Self {
+        let tables =
+            |t: [&PieceTable; NUM_PIECES]| t.map(|t| std::array::from_fn(|i| t.at_index(i)));
+        return Self {
+            piece_values: ALL_PIECES.map(piece_value),
+            mg_tables: tables([
+                &PAWN_TABLE,
+                &KNIGHT_TABLE,
+                &BISHOP_TABLE,
+                &ROOK_TABLE,
+                &QUEEN_TABLE,
+                &KING_TABLE_MID,
+            ]),
+            eg_tables: tables([
+                &PAWN_TABLE_END,
+                &KNIGHT_TABLE_END,
+                &BISHOP_TABLE_END,
+                &ROOK_TABLE_END,
+                &QUEEN_TABLE_END,
+                &KING_TABLE_END,
+            ]),
+        };
+    }
+
+    /// Reads a parameter file, see the type documentation for the format.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ParamsError> {
+        return Self::parse(&std::fs::read_to_string(path)?);
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ParamsError> {
+        std::fs::write(path, self.to_string())?;
+        return Ok(());
+    }
+
+    /// Parses parameters from text, starting from the defaults.
+    pub fn parse(text: &str) -> Result<Self, ParamsError> {
+        let mut entries: Vec<(usize, String, Vec<i32>)> = Vec::new();
+        for (idx, line) in text.lines().enumerate() {
+            let line_no = idx + 1;
+            let line = line.split('#').next().unwrap_or("");
+            let values = match line.split_once('=') {
+                Some((name, values)) => {
+                    entries.push((line_no, name.trim().to_string(), Vec::new()));
+                    values
+                }
+                None => line,
+            };
+            for value in values.split([',', ' ', '\t']).filter(|v| !v.is_empty()) {
+                let (_, name, parsed) = entries.last_mut().ok_or(ParamsError::Parse {
+                    line: line_no,
+                    message: "values before the first parameter name".to_string(),
+                })?;
+                let value = value.parse::<i32>().map_err(|_| ParamsError::Parse {
+                    line: line_no,
+                    message: format!("invalid value \"{}\" for {}", value, name),
+                })?;
+                parsed.push(value);
+            }
+        }
+
+        let mut params = Self::new();
+        for (line, name, values) in entries {
+            params
+                .set(&name, &values)
+                .map_err(|message| ParamsError::Parse { line, message })?;
+        }
+        return Ok(params);
+    }
+
+    fn set(&mut self, name: &str, values: &[i32]) -> Result<(), String> {
+        let (piece, kind) = name
+            .rsplit_once('_')
+            .and_then(|(piece, kind)| Some((PIECE_NAMES.iter().position(|p| *p == piece)?, kind)))
+            .ok_or(format!("unknown parameter {}", name))?;
+        let expected = if kind == "value" { 1 } else { 64 };
+        if values.len() != expected {
+            return Err(format!(
+                "{} needs {} values, got {}",
+                name,
+                expected,
+                values.len()
+            ));
+        }
+        match kind {
+            "value" => self.piece_values[piece] = values[0],
+            "mg" => self.mg_tables[piece].copy_from_slice(values),
+            "eg" => self.eg_tables[piece].copy_from_slice(values),
+            _ => return Err(format!("unknown parameter {}", name)),
+        }
+        return Ok(());
+    }
+
+    /// Every parameter that influences the evaluation as one flat list: the piece values
+    /// (the king's is left out, it never changes hands) followed by the middlegame and the
+    /// endgame tables.
+    pub fn tunable(&self) -> Vec<i32> {
+        let mut values = self.piece_values[..NUM_PIECES - 1].to_vec();
+        values.extend(self.mg_tables.iter().flatten());
+        values.extend(self.eg_tables.iter().flatten());
+        return values;
+    }
+
+    /// The inverse of `tunable`.
+    pub fn set_tunable(&mut self, values: &[i32]) {
+        let (pieces, tables) = values.split_at(NUM_PIECES - 1);
+        self.piece_values[..NUM_PIECES - 1].copy_from_slice(pieces);
+        let (mg, eg) = tables.split_at(NUM_PIECES * 64);
+        for (table, values) in self.mg_tables.iter_mut().zip(mg.chunks(64)) {
+            table.copy_from_slice(values);
+        }
+        for (table, values) in self.eg_tables.iter_mut().zip(eg.chunks(64)) {
+            table.copy_from_slice(values);
+        }
+    }
+
+    /// The value of a piece on a square in both the middlegame and the endgame.
+    pub fn score_piece_position(
+        &self,
+        piece: Piece,
+        color: Color,
+        rank: Rank,
+        file: File,
+    ) -> Score {
+        let idx = table_index(color, rank, file);
+        let p = piece.to_index();
+        let value = self.piece_values[p];
+        return Score::new(
+            value + self.mg_tables[p][idx],
+            value + self.eg_tables[p][idx],
+        );
+    }
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+impl fmt::Display for EvalParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (p, name) in PIECE_NAMES.iter().enumerate() {
+            writeln!(f, "{}_value = {}", name, self.piece_values[p])?;
+            for (kind, table) in [("mg", &self.mg_tables[p]), ("eg", &self.eg_tables[p])] {
+                writeln!(f, "{}_{} =", name, kind)?;
+                for row in table.chunks(8) {
+                    let row: Vec<String> = row.iter().map(|v| format!("{:4}", v)).collect();
+                    writeln!(f, "   {}", row.join(","))?;
+                }
+            }
+            writeln!(f)?;
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::piece_table::score_piece_position;
+    use chess::ALL_SQUARES;
+
+    #[test]
+    fn defaults_match_constants() {
+        let params = EvalParams::new();
+        for piece in ALL_PIECES {
+            for color in [Color::White, Color::Black] {
+                for sq in ALL_SQUARES {
+                    let (rank, file) = (sq.get_rank(), sq.get_file());
+                    assert_eq!(
+                        params.score_piece_position(piece, color, rank, file),
+                        score_piece_position(piece, color, rank, file)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut params = EvalParams::new();
+        params.piece_values[Piece::Knight.to_index()] = 333;
+        params.eg_tables[Piece::Rook.to_index()][7] = -12;
+        assert_eq!(EvalParams::parse(&params.to_string()).unwrap(), params);
+    }
+
+    #[test]
+    fn tunable_round_trip() {
+        let params = EvalParams::new();
+        let mut values = params.tunable();
+        assert_eq!(values.len(), NUM_PIECES - 1 + 2 * NUM_PIECES * 64);
+        values[0] += 10;
+        *values.last_mut().unwrap() -= 5;
+        let mut tuned = params.clone();
+        tuned.set_tunable(&values);
+        assert_eq!(tuned.piece_values[0], params.piece_values[0] + 10);
+        assert_eq!(tuned.eg_tables[5][63], params.eg_tables[5][63] - 5);
+        assert_eq!(tuned.tunable(), values);
+    }
+
+    #[test]
+    fn partial_file_keeps_defaults() {
+        let params = EvalParams::parse("# tuned\nbishop_value = 345 # up from 330\n").unwrap();
+        let mut expected = EvalParams::new();
+        expected.piece_values[Piece::Bishop.to_index()] = 345;
+        assert_eq!(params, expected);
+    }
+
+    #[test]
+    fn errors_point_at_the_line() {
+        let err = EvalParams::parse("pawn_value = 100\n\nelephant_value = 3").unwrap_err();
+        assert!(matches!(err, ParamsError::Parse { line: 3, .. }));
+        let err = EvalParams::parse("rook_mg = 1, 2, 3").unwrap_err();
+        assert_eq!(err.to_string(), "line 1: rook_mg needs 64 values, got 3");
+        let err = EvalParams::parse("queen_value = nine").unwrap_err();
+        assert!(matches!(err, ParamsError::Parse { line: 1, .. }));
+    }
+}
@@ -1,13 +1,43 @@
-use crate::piece_table::{piece_value, score_piece_position};
-use chess::{Board, BoardStatus, ChessMove, Color, File, MoveGen, Piece, Rank, Square, EMPTY};
-use std::collections::HashMap;
+use crate::eval_params::EvalParams;
+use crate::evaluator::{Evaluator, PstEvaluator};
+use crate::game_history::{is_irreversible, GameHistory};
+use crate::move_order::{ordered_tactical_moves, MoveOrderer, MAX_PLY};
+use crate::pawns::pawn_zobrist;
+use crate::piece_table::{phase_weight, piece_value, Score, MAX_PHASE};
+use crate::pruning::PruningParams;
+use crate::pv::PvTable;
+use crate::reductions::{ReductionTable, LMR_MIN_DEPTH, LMR_MIN_MOVES};
+use crate::see::loses_material;
+use crate::transposition::{Bound, TTEntry, TranspositionTable};
+use chess::{
+    Board, BoardStatus, ChessMove, Color, File, MoveGen, Piece, Rank, Square, ALL_PIECES, EMPTY,
+};
 use std::ops::Neg;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 pub const MIN_SCORE: i32 = (i16::MIN) as i32;
+// being mated right away. a mate n plies from the root scores CHECKMATE_SCORE + n for the side
+// that gets mated, so that shorter mates are preferred and the distance can be reported.
 pub const CHECKMATE_SCORE: i32 = MIN_SCORE + 128;
+pub const DRAW_SCORE: i32 = 0;
+// a hundred plies without a capture or a pawn move and either side can claim a draw.
+const FIFTY_MOVE_PLIES: u32 = 100;
+// null-move pruning reduces the depth by this much more than a normal move...
+const NULL_MOVE_REDUCTION: i8 = 2;
+// ...plus one more ply for every this many plies of remaining depth.
+const NULL_MOVE_DEPTH_DIVISOR: i8 = 6;
+// there is no point in a null move when the reduced search would go straight to quiescence.
+const NULL_MOVE_MIN_DEPTH: i8 = 3;
+// no line gets extended more than this many times, or checks back and forth could go on forever.
+const MAX_LINE_EXTENSIONS: u8 = 16;
+// singular extensions: the hash move gets an extra ply when every other move falls short of its
+// score by this much per ply of depth, in a verification search of half the depth.
+const SINGULAR_MIN_DEPTH: i8 = 6;
+const SINGULAR_MARGIN: i32 = 2;
+// how often a finished infinite search checks whether it was told to stop.
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(5);
 
 pub struct PieceEvent {
     pub piece: Piece,
@@ -25,6 +55,8 @@ pub struct MoveEvents {
     pub promotion: Option<PieceEvent>,
     // if you capture a piece, your side is better, right?
     pub capture: Option<PieceEvent>,
+    // if you castle, the rook moves from the first square to the second.
+    pub castle: Option<(Square, Square)>,
 }
 
 impl MoveEvents {
@@ -32,6 +64,7 @@ impl MoveEvents {
         return Self {
             promotion: None,
             capture: None,
+            castle: None,
         };
     }
 
@@ -46,6 +79,16 @@ impl MoveEvents {
             self.capture = Some(PieceEvent::new(p, sq));
         }
     }
+
+    pub fn add_castle(&mut self, rook_from: Square, rook_to: Square) {
+        self.castle = Some((rook_from, rook_to));
+    }
+}
+
+impl Default for MoveEvents {
+    fn default() -> Self {
+        return Self::new();
+    }
 }
 
 pub struct MoveInfo {
@@ -83,36 +126,78 @@ impl MoveInfo {
         let mut move_events = MoveEvents::new();
         move_events.add_promotion(m.get_promotion(), to);
         move_events.add_capture(b.piece_on(to), to);
+        // en passant, the captured pawn isn't on the destination square but next to where we started.
+        if piece == Piece::Pawn && from.get_file() != to.get_file() && b.piece_on(to).is_none() {
+            let victim = Square::make_square(from.get_rank(), to.get_file());
+            move_events.add_capture(Some(Piece::Pawn), victim);
+        }
+        // castling, the king moves two files and takes the rook along with it.
+        if piece == Piece::King
+            && from
+                .get_file()
+                .to_index()
+                .abs_diff(to.get_file().to_index())
+                == 2
+        {
+            let rank = from.get_rank();
+            let (rook_from, rook_to) = if to.get_file() == File::G {
+                (File::H, File::F)
+            } else {
+                (File::A, File::D)
+            };
+            move_events.add_castle(
+                Square::make_square(rank, rook_from),
+                Square::make_square(rank, rook_to),
+            );
+        }
         return Self::new(color_to_move, move_events, from, to, piece);
     }
+
+    /// Captures and promotions change the material balance, so they are the moves worth
+    /// resolving before we trust a static score.
+    pub fn is_tactical(&self) -> bool {
+        return self.move_events.capture.is_some() || self.move_events.promotion.is_some();
+    }
 }
 
-#[derive(Clone, Debug)]
-pub struct BoardState {
+/// A board along with the evaluator used to score it and the evaluator's incremental state.
+pub struct BoardState<E: Evaluator = PstEvaluator> {
     pub board: Board,
-    pub white_position: i32,
-    pub black_position: i32,
+    pub eval: Arc<E>,
+    pub acc: E::State,
+    // plies since the last capture or pawn move, for the fifty-move rule.
+    pub halfmove_clock: u32,
 }
 
-impl BoardState {
-    pub fn new(board: Board, white_position: i32, black_position: i32) -> Self {
+impl BoardState<PstEvaluator> {
+    pub fn from_board(board: Board) -> Self {
+        return Self::with_evaluator(board, Arc::new(PstEvaluator::new()));
+    }
+}
+
+impl<E: Evaluator> BoardState<E> {
+    pub fn with_evaluator(board: Board, eval: Arc<E>) -> Self {
+        let acc = eval.init(&board);
         return Self {
             board,
-            white_position,
-            black_position,
+            eval,
+            acc,
+            halfmove_clock: 0,
         };
     }
 
-    pub fn from_board(board: Board) -> Self {
-        let (white_position, black_position) = score_board_position(&board);
-        return Self::new(board, white_position, black_position);
+    /// Starts the fifty-move count at `clock` instead of 0.
+    pub fn halfmove_clock(mut self, clock: u32) -> Self {
+        self.halfmove_clock = clock;
+        return self;
     }
 
     pub fn board_score(&self) -> i32 {
+        let score = self.eval.evaluate(&self.board, &self.acc);
         if self.board.side_to_move() == Color::White {
-            return self.white_position - self.black_position;
+            return score;
         }
-        return self.black_position - self.white_position;
+        return -score;
     }
 
     // convert a checkmate (no move min) to a score for the side to move
@@ -123,23 +208,45 @@ impl BoardState {
         }
     }
 
-    pub fn apply_move(&self, m: &ChessMove) -> Self {
-        let mut next = self.clone();
-        next.score_position_change(&MoveInfo::from_move(m, &next.board));
-        next.board = next.board.make_move_new(*m);
-        return next;
+    /// Passes the turn to the opponent, None when in check (passing would be illegal).
+    pub fn apply_null_move(&self) -> Option<Self> {
+        let board = self.board.null_move()?;
+        return Some(Self {
+            board,
+            eval: self.eval.clone(),
+            acc: self.acc.clone(),
+            // a pass isn't a real move, don't look for repetitions across it.
+            halfmove_clock: 0,
+        });
     }
 
-    pub fn score_position_change(&mut self, info: &MoveInfo) {
-        let position_diff = score_position_diff(info);
-        let capture_diff = score_capture_diff(info);
-        if info.color_to_move == Color::White {
-            self.white_position += position_diff;
-            self.black_position += capture_diff;
+    pub fn apply_move(&self, m: &ChessMove) -> Self {
+        let info = MoveInfo::from_move(m, &self.board);
+        let halfmove_clock = if is_irreversible(&self.board, *m) {
+            0
         } else {
-            self.black_position += position_diff;
-            self.white_position += capture_diff;
-        }
+            self.halfmove_clock + 1
+        };
+        let board = self.board.make_move_new(*m);
+        let mut acc = self.acc.clone();
+        self.eval.update(&mut acc, &info, &board);
+        return Self {
+            board,
+            eval: self.eval.clone(),
+            acc,
+            halfmove_clock,
+        };
+    }
+}
+
+impl<E: Evaluator> Clone for BoardState<E> {
+    fn clone(&self) -> Self {
+        return Self {
+            board: self.board,
+            eval: self.eval.clone(),
+            acc: self.acc.clone(),
+            halfmove_clock: self.halfmove_clock,
+        };
     }
 }
 
@@ -148,6 +255,10 @@ pub struct NegaMaxResult {
     pub nodes: u64,
     pub score: i32,
     pub is_complete: bool,
+    // deepest ply reached, including the quiescence search (only filled in at the top level).
+    pub seldepth: usize,
+    // the line the search expects to be played (only filled in at the top level).
+    pub pv: Vec<ChessMove>,
 }
 
 impl NegaMaxResult {
@@ -156,6 +267,8 @@ impl NegaMaxResult {
             nodes: 1,
             score,
             is_complete: false,
+            seldepth: 0,
+            pv: Vec::new(),
         };
     }
 
@@ -167,9 +280,8 @@ impl NegaMaxResult {
 
     pub fn complete(self) -> Self {
         return Self {
-            nodes: self.nodes,
-            score: self.score,
             is_complete: true,
+            ..self
         };
     }
     pub fn nodes(mut self, n: u64) -> Self {
@@ -182,9 +294,8 @@ impl Neg for NegaMaxResult {
     type Output = NegaMaxResult;
     fn neg(self) -> Self::Output {
         return NegaMaxResult {
-            nodes: self.nodes,
             score: -self.score,
-            is_complete: self.is_complete,
+            ..self
         };
     }
 }
@@ -199,7 +310,23 @@ pub enum NegaMaxDepth {
 pub struct NegaMaxOptions {
     depth: NegaMaxDepth,
     mtime: Option<Instant>,
+    soft_mtime: Option<Instant>,
     signal: Option<Arc<AtomicBool>>,
+    tt: Option<Arc<TranspositionTable>>,
+    lmr: Arc<ReductionTable>,
+    pruning: PruningParams,
+    // the (alpha, beta) window the search starts with.
+    window: (i32, i32),
+    history: Arc<GameHistory>,
+    extensions: bool,
+    // stop once a search context has searched this many nodes.
+    nodes: Option<u64>,
+    // stop once a mate in this many moves or less is found.
+    mate: Option<u32>,
+    // the only root moves to search, all of them when empty.
+    search_moves: Arc<Vec<ChessMove>>,
+    // "go infinite": the result is held back until the signal is set.
+    infinite: bool,
 }
 
 impl NegaMaxOptions {
@@ -207,34 +334,120 @@ impl NegaMaxOptions {
         Self {
             depth: NegaMaxDepth::Infinite,
             mtime: None,
+            soft_mtime: None,
             signal: None,
+            tt: None,
+            lmr: Arc::new(ReductionTable::default()),
+            pruning: PruningParams::default(),
+            window: (MIN_SCORE, -MIN_SCORE),
+            history: Arc::new(GameHistory::new()),
+            extensions: true,
+            nodes: None,
+            mate: None,
+            infinite: false,
+            search_moves: Arc::new(Vec::new()),
         }
     }
 
     pub fn depth(&self, depth: i8) -> Self {
-        return Self {
-            depth: NegaMaxDepth::Finite(depth),
-            mtime: self.mtime,
-            signal: self.signal.clone(),
-        };
+        let mut next = self.clone();
+        next.depth = NegaMaxDepth::Finite(depth);
+        return next;
     }
 
     pub fn mtime(&self, limit: u64) -> Self {
-        // self.mtime = Some(Instant::now() + Duration::from_millis(limit));
-        return Self {
-            depth: self.depth.clone(),
-            mtime: Some(Instant::now() + Duration::from_millis(limit)),
-            signal: self.signal.clone(),
-        };
+        let mut next = self.clone();
+        next.mtime = Some(Instant::now() + Duration::from_millis(limit));
+        return next;
+    }
+
+    /// A deadline that is only checked between iterative deepening iterations, unlike `mtime`
+    /// which aborts the search in the middle of an iteration.
+    pub fn soft_mtime(&self, limit: u64) -> Self {
+        let mut next = self.clone();
+        next.soft_mtime = Some(Instant::now() + Duration::from_millis(limit));
+        return next;
     }
 
     pub fn signal(&self, signal: Arc<AtomicBool>) -> Self {
-        // self.signal = Some(signal);
-        return Self {
-            depth: self.depth.clone(),
-            mtime: self.mtime,
-            signal: Some(signal),
-        };
+        let mut next = self.clone();
+        next.signal = Some(signal);
+        return next;
+    }
+
+    /// Share a transposition table with the search. Every clone of these options
+    /// (e.g., one per worker thread) will probe and store into the same table.
+    pub fn tt(&self, tt: Arc<TranspositionTable>) -> Self {
+        let mut next = self.clone();
+        next.tt = Some(tt);
+        return next;
+    }
+
+    /// Late move reductions to use, `ReductionTable::disabled()` turns them off.
+    pub fn lmr(&self, lmr: Arc<ReductionTable>) -> Self {
+        let mut next = self.clone();
+        next.lmr = lmr;
+        return next;
+    }
+
+    /// Futility, reverse futility and late move pruning near the horizon,
+    /// `PruningParams::disabled()` turns them off.
+    pub fn pruning(&self, pruning: PruningParams) -> Self {
+        let mut next = self.clone();
+        next.pruning = pruning;
+        return next;
+    }
+
+    /// Only look for scores between `alpha` and `beta`. Anything outside the window comes back
+    /// as a bound: at most `alpha` if the search failed low, at least `beta` if it failed high.
+    pub fn window(&self, alpha: i32, beta: i32) -> Self {
+        let mut next = self.clone();
+        next.window = (alpha, beta);
+        return next;
+    }
+
+    /// The game so far, positions from it that come up again in the search are draws.
+    pub fn history(&self, history: GameHistory) -> Self {
+        let mut next = self.clone();
+        next.history = Arc::new(history);
+        return next;
+    }
+
+    /// Whether checks, forced replies and singular moves are searched a ply deeper.
+    pub fn extensions(&self, extensions: bool) -> Self {
+        let mut next = self.clone();
+        next.extensions = extensions;
+        return next;
+    }
+
+    /// Stop after searching `nodes` nodes. The count covers every search made with the same
+    /// `SearchContext`, so it is a budget for a whole iterative deepening run.
+    pub fn nodes(&self, nodes: u64) -> Self {
+        let mut next = self.clone();
+        next.nodes = Some(nodes);
+        return next;
+    }
+
+    /// Stop iterative deepening once a mate in `moves` moves or less is found.
+    pub fn mate(&self, moves: u32) -> Self {
+        let mut next = self.clone();
+        next.mate = Some(moves);
+        return next;
+    }
+
+    /// Keep the result of a search that finished on its own until the signal is set, as
+    /// "go infinite" expects.
+    pub fn infinite(&self, infinite: bool) -> Self {
+        let mut next = self.clone();
+        next.infinite = infinite;
+        return next;
+    }
+
+    /// Only search these moves at the root, an empty list searches all of them.
+    pub fn search_moves(&self, moves: Vec<ChessMove>) -> Self {
+        let mut next = self.clone();
+        next.search_moves = Arc::new(moves);
+        return next;
     }
 
     pub fn is_finite(&self) -> bool {
@@ -256,88 +469,589 @@ impl NegaMaxOptions {
         return self.mtime;
     }
 
+    pub fn get_soft_mtime(&self) -> Option<Instant> {
+        return self.soft_mtime;
+    }
+
     pub fn get_signal(&self) -> Option<Arc<AtomicBool>> {
         return self.signal.clone();
     }
+
+    pub fn get_tt(&self) -> Option<Arc<TranspositionTable>> {
+        return self.tt.clone();
+    }
+
+    pub fn get_lmr(&self) -> Arc<ReductionTable> {
+        return self.lmr.clone();
+    }
+
+    pub fn get_pruning(&self) -> PruningParams {
+        return self.pruning;
+    }
+
+    pub fn get_history(&self) -> Arc<GameHistory> {
+        return self.history.clone();
+    }
+
+    pub fn get_nodes(&self) -> Option<u64> {
+        return self.nodes;
+    }
+
+    pub fn get_mate(&self) -> Option<u32> {
+        return self.mate;
+    }
+
+    /// Blocks an infinite search that ran out of things to search until it is told to stop.
+    pub fn wait_for_stop(&self) {
+        // without a signal nothing could ever stop it.
+        if !self.infinite || self.signal.is_none() {
+            return;
+        }
+        while !signal_must_stop(&self.signal) {
+            std::thread::sleep(STOP_POLL_INTERVAL);
+        }
+    }
+
+    /// The legal moves of `board` the search may play.
+    pub fn root_moves(&self, board: &Board) -> Vec<ChessMove> {
+        return MoveGen::new_legal(board)
+            .filter(|m| self.is_root_move(m))
+            .collect();
+    }
+
+    fn is_root_move(&self, m: &ChessMove) -> bool {
+        return self.search_moves.is_empty() || self.search_moves.contains(m);
+    }
+
+    /// Whether a search with `ctx` has to stop, because it ran out of time or nodes or
+    /// was told to.
+    pub fn must_stop(&self, ctx: &SearchContext) -> bool {
+        return task_must_stop(&self.mtime, &self.signal)
+            || self.nodes.is_some_and(|n| ctx.nodes >= n);
+    }
+}
+
+impl Default for NegaMaxOptions {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+/// Per-thread search state that outlives a single call to the search, so that iterative
+/// deepening iterations can build on what the previous ones learned.
+#[derive(Clone, Default)]
+pub struct SearchContext {
+    pub orderer: MoveOrderer,
+    pub pv: PvTable,
+    pub seldepth: usize,
+    // whether the move that led to each ply was a null move.
+    pub null_move: Vec<bool>,
+    // hashes of the game before the root followed by the positions on the current line.
+    positions: Vec<u64>,
+    // how many of `positions` come from the game rather than the search.
+    game_len: usize,
+    // how many times the line leading to each ply was extended.
+    extensions: Vec<u8>,
+    // the move left out at each ply while checking whether the hash move is singular.
+    excluded: Vec<Option<ChessMove>>,
+    /// Root moves the search leaves out, e.g., the ones already reported as better lines in
+    /// a multipv search.
+    pub root_excluded: Vec<ChessMove>,
+    // nodes searched with this context so far.
+    nodes: u64,
+}
+
+impl SearchContext {
+    pub fn new() -> Self {
+        return Self {
+            orderer: MoveOrderer::new(),
+            pv: PvTable::new(),
+            seldepth: 0,
+            null_move: vec![false; MAX_PLY + 1],
+            positions: Vec::new(),
+            game_len: 0,
+            extensions: vec![0; MAX_PLY + 1],
+            excluded: vec![None; MAX_PLY + 1],
+            root_excluded: Vec::new(),
+            nodes: 0,
+        };
+    }
+
+    /// How many nodes were searched with this context so far.
+    pub fn nodes(&self) -> u64 {
+        return self.nodes;
+    }
+
+    // makes `hash` the position at `ply` of the current line.
+    fn enter_position(&mut self, ply: usize, hash: u64) {
+        self.positions.truncate(self.game_len + ply);
+        self.positions.push(hash);
+    }
+
+    // whether the position just entered already occurred since the last irreversible move.
+    fn is_repetition(&self, halfmove_clock: u32) -> bool {
+        let current = self.positions.len() - 1;
+        let hash = self.positions[current];
+        // only positions with the same side to move can be equal.
+        let oldest = current.saturating_sub(halfmove_clock as usize);
+        let mut i = current;
+        while i >= oldest + 2 {
+            i -= 2;
+            if self.positions[i] == hash {
+                return true;
+            }
+        }
+        return false;
+    }
+
+    fn after_null_move(&self, ply: usize) -> bool {
+        return self.null_move.get(ply).copied().unwrap_or(false);
+    }
 }
 
 /// The default negamax with rely on iterative deepening in order to support time limits.
 /// If you need to just search an exact depth it might be more efficent to call nega_max_with_depth instead.
-pub fn nega_max(state: BoardState, opts: NegaMaxOptions) -> NegaMaxResult {
+pub fn nega_max<E: Evaluator>(state: BoardState<E>, opts: NegaMaxOptions) -> NegaMaxResult {
+    return nega_max_with_context(state, opts, &mut SearchContext::new());
+}
+
+/// Same as `nega_max` but keeps the killer and history tables in `ctx` for the next search.
+pub fn nega_max_with_context<E: Evaluator>(
+    state: BoardState<E>,
+    opts: NegaMaxOptions,
+    ctx: &mut SearchContext,
+) -> NegaMaxResult {
     let depth = opts.get_depth();
-    let time = opts.get_mtime();
-    let signal = opts.get_signal();
-    return nega_max_proper(state, depth, MIN_SCORE, -MIN_SCORE, &time, &signal);
+    let (alpha, beta) = opts.window;
+    ctx.seldepth = 0;
+    ctx.positions.clear();
+    ctx.positions.extend_from_slice(opts.history.hashes());
+    ctx.game_len = ctx.positions.len();
+    ctx.extensions[0] = 0;
+    let mut result = nega_max_proper(state, depth, 0, alpha, beta, &opts, ctx);
+    result.seldepth = ctx.seldepth;
+    result.pv = ctx.pv.line(0).to_vec();
+    return result;
 }
 
-fn nega_max_proper(
-    state: BoardState,
+fn nega_max_proper<E: Evaluator>(
+    state: BoardState<E>,
     depth: i8,
+    ply: usize,
     mut alpha: i32,
-    beta: i32,
-    time: &Option<Instant>,
-    signal: &Option<Arc<AtomicBool>>,
+    mut beta: i32,
+    opts: &NegaMaxOptions,
+    ctx: &mut SearchContext,
 ) -> NegaMaxResult {
-    let base_score = state.board_score();
-    // if we can't go further, return the score of the board as is.
-    if depth == 0 {
-        if state.board.status() == BoardStatus::Checkmate {
-            return NegaMaxResult::new(CHECKMATE_SCORE).complete();
+    ctx.pv.clear(ply);
+    ctx.nodes += 1;
+    let hash = state.board.get_hash();
+    ctx.enter_position(ply, hash);
+    // a repeated position can be repeated again and again, so it's as good as a draw. the root
+    // always has to come up with a move though.
+    if ply > 0
+        && (state.halfmove_clock >= FIFTY_MOVE_PLIES || ctx.is_repetition(state.halfmove_clock))
+    {
+        return NegaMaxResult::new(DRAW_SCORE).complete();
+    }
+    // mate distance pruning: nothing found from here on can beat a mate that is already known
+    // closer to the root.
+    if ply > 0 {
+        alpha = alpha.max(mated_in(ply));
+        beta = beta.min(mate_in(ply + 1));
+        if alpha >= beta {
+            return NegaMaxResult::new(alpha).complete();
         }
-        return NegaMaxResult::new(base_score).complete();
     }
+    // nodes searched with a null window only have to tell whether the score is above or below
+    // it, which is where pruning on a guess is cheap to get wrong.
+    let is_pv = beta - alpha > 1;
+    // if we can't go further, settle any pending captures before trusting the score of the board.
+    if depth <= 0 || ply >= MAX_PLY {
+        return quiescence(&state, ply, alpha, beta, opts, ctx);
+    }
+
+    let base_score = state.board_score();
 
     // if we have to leave without getting deep enough, return the score of the board as is (incomplete)
-    if task_must_stop(time, signal) {
+    if opts.must_stop(ctx) {
         return NegaMaxResult::new(base_score);
     }
 
-    let mut max = NegaMaxResult::new(MIN_SCORE);
+    // a previous search of this position to at least the same depth can either answer
+    // the question outright or narrow the window we need to look at.
+    let original_alpha = alpha;
+    let excluded = ctx.excluded[ply];
+    // a search that leaves out root moves doesn't know the real score of the position.
+    let partial_root = ply == 0 && (!ctx.root_excluded.is_empty() || !opts.search_moves.is_empty());
+    let tt_entry = opts.tt.as_ref().and_then(|t| t.probe(hash));
+    let tt_move = tt_entry.and_then(|e| e.best_move);
+    if let Some(entry) = tt_entry {
+        // the root always searches, it has to come back with a move. the entry also can't
+        // answer for a search that leaves a move out.
+        if entry.depth >= depth && ply > 0 && excluded.is_none() {
+            let score = score_from_tt(entry.score, ply);
+            match entry.bound {
+                Bound::Exact => return NegaMaxResult::new(score).complete(),
+                Bound::Lower => alpha = alpha.max(score),
+                Bound::Upper => beta = beta.min(score),
+            }
+            if alpha >= beta {
+                return NegaMaxResult::new(score).complete();
+            }
+        }
+    }
 
-    for m in MoveGen::new_legal(&state.board) {
-        let local = -nega_max_proper(state.apply_move(&m), depth - 1, -beta, -alpha, time, signal);
-        max = max.max_join(local);
-        alpha = alpha.max(max.score);
-        if alpha >= beta {
-            return max.complete();
+    let in_check = *state.board.checkers() != EMPTY;
+
+    // reverse futility: close to the horizon, a position that is already well above beta
+    // without moving is not going to fall below it with the few plies left.
+    if let Some(margin) = opts
+        .pruning
+        .reverse_futility_margin(depth)
+        .filter(|_| !is_pv && ply > 0 && excluded.is_none() && !in_check && !is_mate_score(beta))
+    {
+        if base_score - margin >= beta {
+            return NegaMaxResult::new(base_score).complete();
         }
+    }
+
+    // null move: if we could pass the turn and the opponent still can't get below beta with a
+    // reduced search, a real move will almost surely do at least as well. That doesn't hold in
+    // zugzwang, which is common when only the king and pawns are left to move.
+    if ply > 0
+        && depth >= NULL_MOVE_MIN_DEPTH
+        && excluded.is_none()
+        && !in_check
+        && !ctx.after_null_move(ply)
+        && has_non_pawn_material(&state.board)
+        && base_score >= beta
+    {
+        if let Some(null_state) = state.apply_null_move() {
+            let reduction = NULL_MOVE_REDUCTION + depth / NULL_MOVE_DEPTH_DIVISOR;
+            let null_depth = (depth - 1 - reduction).max(0);
+            ctx.null_move[ply + 1] = true;
+            ctx.extensions[ply + 1] = ctx.extensions[ply];
+            let local =
+                -nega_max_proper(null_state, null_depth, ply + 1, -beta, -beta + 1, opts, ctx);
+            ctx.null_move[ply + 1] = false;
+            if !local.is_complete {
+                return NegaMaxResult::new(base_score).nodes(local.nodes + 1);
+            }
+            if local.score >= beta {
+                // don't trust a mate found without actually moving.
+                let score = if is_mate_score(local.score) {
+                    beta
+                } else {
+                    local.score
+                };
+                return NegaMaxResult::new(score).nodes(local.nodes + 1).complete();
+            }
+            ctx.pv.clear(ply + 1);
+        }
+    }
+
+    // singular extension: if the hash move was good enough to cut off before and nothing else
+    // comes close, the position hinges on it and it deserves a closer look.
+    let mut singular_move = None;
+    let mut singular_nodes = 0;
+    if let Some(entry) = tt_entry.filter(|e| {
+        opts.extensions
+            && ply > 0
+            && excluded.is_none()
+            && depth >= SINGULAR_MIN_DEPTH
+            && e.best_move.is_some()
+            && e.bound != Bound::Upper
+            && e.depth >= depth - 3
+            && !is_mate_score(e.score)
+    }) {
+        let singular_beta = score_from_tt(entry.score, ply) - SINGULAR_MARGIN * depth as i32;
+        ctx.excluded[ply] = entry.best_move;
+        let local = nega_max_proper(
+            state.clone(),
+            (depth - 1) / 2,
+            ply,
+            singular_beta - 1,
+            singular_beta,
+            opts,
+            ctx,
+        );
+        ctx.excluded[ply] = None;
+        if !local.is_complete {
+            return NegaMaxResult::new(base_score).nodes(local.nodes + 1);
+        }
+        singular_nodes = local.nodes;
+        if local.score < singular_beta {
+            singular_move = entry.best_move;
+        }
+    }
+
+    let moves = ctx.orderer.ordered_moves(&state.board, tt_move, ply);
+    let mut max = NegaMaxResult::new(MIN_SCORE).nodes(singular_nodes + 1);
+    let mut best_move = None;
+    // futility and late move pruning skip quiet moves once there's a move to fall back on, but
+    // never when the side to move is in trouble or might be getting mated.
+    let can_prune = !is_pv && !in_check && !is_mate_score(alpha);
+    let futility_margin = opts.pruning.futility_margin(depth).filter(|_| can_prune);
+    let late_move_count = opts.pruning.late_move_count(depth).filter(|_| can_prune);
+
+    for (i, m) in moves.iter().enumerate() {
+        if excluded == Some(*m)
+            || (ply == 0 && (ctx.root_excluded.contains(m) || !opts.is_root_move(m)))
+        {
+            continue;
+        }
+        let is_quiet = !MoveInfo::from_move(m, &state.board).is_tactical();
+        let child = state.apply_move(m);
+        let gives_check = *child.board.checkers() != EMPTY;
+        if best_move.is_some() && is_quiet && !gives_check {
+            // futility: not even a generous positional gain brings the score up to alpha.
+            if futility_margin.is_some_and(|margin| base_score + margin <= alpha) {
+                continue;
+            }
+            // late move pruning: with good ordering, a quiet move this far down the list is
+            // hardly ever the one that matters.
+            if late_move_count.is_some_and(|count| i >= count) {
+                continue;
+            }
+        }
+        // extensions: checks, the only legal move and a singular hash move are searched a ply
+        // deeper, as long as the line has some extensions left.
+        let extension = if opts.extensions
+            && ctx.extensions[ply] < MAX_LINE_EXTENSIONS
+            && (gives_check || moves.len() == 1 || singular_move == Some(*m))
+        {
+            1
+        } else {
+            0
+        };
+        ctx.extensions[ply + 1] = ctx.extensions[ply] + extension as u8;
+        let new_depth = depth - 1 + extension;
+        let local = if best_move.is_none() {
+            -nega_max_proper(child, new_depth, ply + 1, -beta, -alpha, opts, ctx)
+        } else {
+            // principal variation search: the first move is most likely the best, so the others
+            // only have to prove they aren't better with a null window around alpha. Late quiet
+            // moves get to prove it with a reduced search first.
+            let reduction = if depth >= LMR_MIN_DEPTH
+                && i >= LMR_MIN_MOVES
+                && is_quiet
+                && !in_check
+                && !gives_check
+            {
+                opts.lmr.reduction(depth, i).clamp(0, depth - 2)
+            } else {
+                0
+            };
+            let mut local = -nega_max_proper(
+                child.clone(),
+                new_depth - reduction,
+                ply + 1,
+                -alpha - 1,
+                -alpha,
+                opts,
+                ctx,
+            );
+            if local.is_complete && local.score > alpha && reduction > 0 {
+                let nodes = local.nodes;
+                local = -nega_max_proper(
+                    child.clone(),
+                    new_depth,
+                    ply + 1,
+                    -alpha - 1,
+                    -alpha,
+                    opts,
+                    ctx,
+                );
+                local.nodes += nodes;
+            }
+            if local.is_complete && local.score > alpha && local.score < beta {
+                let nodes = local.nodes;
+                local = -nega_max_proper(child, new_depth, ply + 1, -beta, -alpha, opts, ctx);
+                local.nodes += nodes;
+            }
+            local
+        };
         // if we didn't get to the end of the loop, we need
         // to return the score for the board when we entered,
         // because we don't know what the best move for the opponent would have been.
-        if task_must_stop(time, signal) {
+        if !local.is_complete {
+            return NegaMaxResult::new(base_score).max_join(max);
+        }
+        if best_move.is_none() || local.score > max.score {
+            best_move = Some(*m);
+        }
+        if local.score > alpha {
+            ctx.pv.update(ply, *m);
+        }
+        max = max.max_join(local);
+        alpha = alpha.max(max.score);
+        if alpha >= beta {
+            if is_quiet {
+                ctx.orderer.record_cutoff(*m, depth, ply);
+            }
+            break;
+        }
+        if opts.must_stop(ctx) {
             return NegaMaxResult::new(base_score).max_join(max);
         }
     }
 
     // handle the case where the board was in checkmate or stalemate (i.e., had no moves)
-    if max.nodes == 1 {
+    if moves.is_empty() {
         if *state.board.checkers() == EMPTY {
             return NegaMaxResult::new(0).complete();
         } else {
-            return NegaMaxResult::new(CHECKMATE_SCORE - depth as i32).complete();
+            return NegaMaxResult::new(mated_in(ply)).complete();
+        }
+    }
+
+    if let Some(t) = opts
+        .tt
+        .as_ref()
+        .filter(|_| excluded.is_none() && !partial_root)
+    {
+        let bound = if max.score <= original_alpha {
+            Bound::Upper
+        } else if max.score >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        let score = score_to_tt(max.score, ply);
+        t.store(hash, TTEntry::new(best_move, depth, bound, score));
+    }
+
+    return max.complete();
+}
+
+/// Searches only captures and promotions from a horizon node until the position is quiet, so
+/// that the static score is never taken in the middle of an exchange. When the side to move is
+/// in check every evasion is searched instead, which also lets us see mates at the horizon.
+pub fn quiescence<E: Evaluator>(
+    state: &BoardState<E>,
+    ply: usize,
+    mut alpha: i32,
+    beta: i32,
+    opts: &NegaMaxOptions,
+    ctx: &mut SearchContext,
+) -> NegaMaxResult {
+    ctx.seldepth = ctx.seldepth.max(ply);
+    ctx.nodes += 1;
+    // checks and evasions can go on for a long time, a line this long settles for the board.
+    if ply >= MAX_PLY {
+        return NegaMaxResult::new(state.board_score()).complete();
+    }
+    if opts.must_stop(ctx) {
+        return NegaMaxResult::new(state.board_score());
+    }
+    let in_check = *state.board.checkers() != EMPTY;
+    let mut max = NegaMaxResult::new(MIN_SCORE);
+
+    if !in_check {
+        // stand pat: the side to move is never forced to capture, so the static score is a lower bound.
+        let stand_pat = state.board_score();
+        if stand_pat >= beta {
+            return NegaMaxResult::new(stand_pat).complete();
+        }
+        alpha = alpha.max(stand_pat);
+        max.score = stand_pat;
+    }
+
+    let moves: Vec<ChessMove> = if in_check {
+        MoveGen::new_legal(&state.board).collect()
+    } else {
+        ordered_tactical_moves(&state.board)
+    };
+
+    for m in moves.iter() {
+        // a capture that loses material won't raise the score above standing pat.
+        if !in_check && loses_material(&state.board, *m) {
+            continue;
+        }
+        let local = -quiescence(&state.apply_move(m), ply + 1, -beta, -alpha, opts, ctx);
+        if !local.is_complete {
+            return NegaMaxResult::new(state.board_score()).max_join(max);
+        }
+        max = max.max_join(local);
+        alpha = alpha.max(max.score);
+        if alpha >= beta {
+            break;
         }
     }
 
-    return max.complete(); //
+    if in_check && moves.is_empty() {
+        return NegaMaxResult::new(mated_in(ply)).complete();
+    }
+
+    return max.complete();
+}
+
+/// Whether the side to move has anything besides its king and pawns.
+pub fn has_non_pawn_material(board: &Board) -> bool {
+    let pawns_and_kings = *board.pieces(Piece::Pawn) | *board.pieces(Piece::King);
+    return *board.color_combined(board.side_to_move()) & !pawns_and_kings != EMPTY;
+}
+
+/// Whether a score means one of the sides gets mated.
+pub fn is_mate_score(score: i32) -> bool {
+    return score.abs() >= -CHECKMATE_SCORE - MAX_PLY as i32;
+}
+
+/// The score of the side to move when it gets mated `ply` plies from the root.
+pub fn mated_in(ply: usize) -> i32 {
+    return CHECKMATE_SCORE + ply as i32;
+}
+
+/// The score of the side to move when it mates `ply` plies from the root.
+pub fn mate_in(ply: usize) -> i32 {
+    return -mated_in(ply);
+}
+
+/// The number of moves until mate for a mate score, negative when the side to move is the one
+/// getting mated.
+pub fn mate_distance(score: i32) -> Option<i32> {
+    if !is_mate_score(score) {
+        return None;
+    }
+    let plies = -CHECKMATE_SCORE - score.abs();
+    let moves = (plies + 1) / 2;
+    return Some(if score > 0 { moves } else { -moves });
+}
+
+// mate scores count plies from the root, but the table is shared by searches from different
+// roots. they are stored counting from the position itself and converted back on the way out.
+fn score_to_tt(score: i32, ply: usize) -> i32 {
+    if is_mate_score(score) {
+        return score + score.signum() * ply as i32;
+    }
+    return score;
+}
+
+fn score_from_tt(score: i32, ply: usize) -> i32 {
+    if is_mate_score(score) {
+        return score - score.signum() * ply as i32;
+    }
+    return score;
 }
 
 /// returns the change in positional score after a capture relative to the opponent
-pub fn score_capture_diff(info: &MoveInfo) -> i32 {
+pub fn score_capture_diff(info: &MoveInfo, params: &EvalParams) -> Score {
     let capture_score = info.move_events.capture.as_ref().map(|c| {
-        score_piece_position(
+        params.score_piece_position(
             c.piece,
             info.color_of_opponent,
             c.sq.get_rank(),
             c.sq.get_file(),
         )
     });
-    return -capture_score.unwrap_or(0);
+    return -capture_score.unwrap_or_default();
 }
 
 /// Returns the position change from the perspective of the color to move
-pub fn score_position_diff(info: &MoveInfo) -> i32 {
+pub fn score_position_diff(info: &MoveInfo, params: &EvalParams) -> Score {
     // the score of the original position of the piece.
-    let start_score = score_piece_position(
+    let start_score = params.score_piece_position(
         info.piece,
         info.color_to_move,
         info.from.get_rank(),
@@ -347,7 +1061,7 @@ pub fn score_position_diff(info: &MoveInfo) -> i32 {
     // if it is a promotion, we need to calculate the score of the new piece
     // at the new square
     if let Some(ref promo) = info.move_events.promotion {
-        let promotion_score = score_piece_position(
+        let promotion_score = params.score_piece_position(
             promo.piece,
             info.color_to_move,
             promo.sq.get_rank(),
@@ -356,39 +1070,99 @@ pub fn score_position_diff(info: &MoveInfo) -> i32 {
         return promotion_score - start_score;
     }
 
-    let end_score = score_piece_position(
+    let end_score = params.score_piece_position(
         info.piece,
         info.color_to_move,
         info.to.get_rank(),
         info.to.get_file(),
     );
 
-    return end_score - start_score;
+    // when castling the rook changes squares too.
+    let castle_score = info
+        .move_events
+        .castle
+        .map(|(from, to)| {
+            params.score_piece_position(
+                Piece::Rook,
+                info.color_to_move,
+                to.get_rank(),
+                to.get_file(),
+            ) - params.score_piece_position(
+                Piece::Rook,
+                info.color_to_move,
+                from.get_rank(),
+                from.get_file(),
+            )
+        })
+        .unwrap_or_default();
+
+    return end_score - start_score + castle_score;
 }
 
-pub fn score_board_position(board: &Board) -> (i32, i32) {
-    let mut white = 0;
-    let mut black = 0;
+/// Returns the bits of the pawn key that change with the move.
+pub fn pawn_key_diff(info: &MoveInfo) -> u64 {
+    let mut diff = 0;
+    if info.piece == Piece::Pawn {
+        diff ^= pawn_zobrist(info.color_to_move, info.from);
+        if info.move_events.promotion.is_none() {
+            diff ^= pawn_zobrist(info.color_to_move, info.to);
+        }
+    }
+    if let Some(capture) = &info.move_events.capture {
+        if capture.piece == Piece::Pawn {
+            diff ^= pawn_zobrist(info.color_of_opponent, capture.sq);
+        }
+    }
+    return diff;
+}
+
+/// Returns the change in game phase, captured pieces leave the board and promoted ones join it.
+pub fn score_phase_diff(info: &MoveInfo) -> i32 {
+    let captured = info
+        .move_events
+        .capture
+        .as_ref()
+        .map(|c| phase_weight(c.piece))
+        .unwrap_or(0);
+    let promoted = info
+        .move_events
+        .promotion
+        .as_ref()
+        .map(|p| phase_weight(p.piece))
+        .unwrap_or(0);
+    return promoted - captured;
+}
+
+pub fn score_board_position(board: &Board, params: &EvalParams) -> (Score, Score) {
+    let mut white = Score::default();
+    let mut black = Score::default();
     for r in 0..8 {
         for f in 0..8 {
             let rank = Rank::from_index(r);
             let file = File::from_index(f);
             let square = Square::make_square(rank, file);
-            board.piece_on(square).map(|piece| {
-                board.color_on(square).map(|c| {
-                    let score = score_piece_position(piece, c, rank, file);
-                    if c == Color::White {
-                        white += score;
-                    } else {
-                        black += score;
-                    }
-                });
-            });
+            if let (Some(piece), Some(c)) = (board.piece_on(square), board.color_on(square)) {
+                let score = params.score_piece_position(piece, c, rank, file);
+                if c == Color::White {
+                    white += score;
+                } else {
+                    black += score;
+                }
+            }
         }
     }
     return (white, black);
 }
 
+/// The game phase of a board computed from its remaining non-pawn material.
+pub fn board_phase(board: &Board) -> i32 {
+    let phase: i32 = ALL_PIECES
+        .iter()
+        .map(|p| board.pieces(*p).popcnt() as i32 * phase_weight(*p))
+        .sum();
+    return phase.min(MAX_PHASE);
+}
+
 pub fn score_board_material(board: &Board) -> (i32, i32) {
     let mut white = 0;
     let mut black = 0;
@@ -397,16 +1171,14 @@ pub fn score_board_material(board: &Board) -> (i32, i32) {
             let rank = Rank::from_index(r);
             let file = File::from_index(f);
             let square = Square::make_square(rank, file);
-            board.piece_on(square).map(|piece| {
-                board.color_on(square).map(|c| {
-                    let score = piece_value(piece);
-                    if c == Color::White {
-                        white += score;
-                    } else {
-                        black += score;
-                    }
-                });
-            });
+            if let (Some(piece), Some(c)) = (board.piece_on(square), board.color_on(square)) {
+                let score = piece_value(piece);
+                if c == Color::White {
+                    white += score;
+                } else {
+                    black += score;
+                }
+            }
         }
     }
     return (white, black);
@@ -433,7 +1205,7 @@ fn time_must_stop(time: &Option<Instant>) -> bool {
 #[cfg(test)]
 mod test {
     use super::*;
-    // use crate::piece_table::{PAWN, PAWN_TABLE};
+    use crate::piece_table::{score_piece_position, PAWN};
     use chess::{Board, File, Rank};
     use std::str::FromStr;
 
@@ -453,7 +1225,7 @@ mod test {
         // test complete
         let e = NegaMaxResult::new(30);
         let f = e.complete();
-        assert_eq!(f.is_complete, true);
+        assert!(f.is_complete);
 
         // test nodes
         let g = NegaMaxResult::new(40).nodes(100);
@@ -468,15 +1240,15 @@ mod test {
                 .unwrap();
         let m = ChessMove::from_san(&board, "exd5").unwrap();
         let info = MoveInfo::from_move(&m, &board);
-        assert_eq!(info.move_events.capture.is_some(), true);
-        assert_eq!(info.move_events.promotion.is_none(), true);
+        assert!(info.move_events.capture.is_some());
+        assert!(info.move_events.promotion.is_none());
 
         let black_loss = score_piece_position(Piece::Pawn, Color::Black, Rank::Fifth, File::D);
-        assert_eq!(black_loss, 120);
+        assert_eq!(black_loss.mg, 120);
 
         let white_gain = score_piece_position(Piece::Pawn, Color::White, Rank::Fifth, File::D)
             - score_piece_position(Piece::Pawn, Color::White, Rank::Fourth, File::E);
-        assert_eq!(white_gain, 5);
+        assert_eq!(white_gain.mg, 5);
     }
 
     #[test]
@@ -490,14 +1262,44 @@ mod test {
             Some(Piece::Queen),
         );
         let info = MoveInfo::from_move(&m, &board);
-        assert_eq!(info.move_events.capture.is_some(), true);
-        assert_eq!(info.move_events.promotion.is_some(), true);
-        board
+        assert!(info.move_events.capture.is_some());
+        assert!(info.move_events.promotion.is_some());
+        let promoted = board
             .make_move_new(m)
-            .piece_on(Square::make_square(Rank::Eighth, File::D))
-            .map(|p| {
-                assert_eq!(p, Piece::Queen);
-            });
+            .piece_on(Square::make_square(Rank::Eighth, File::D));
+        assert_eq!(promoted, Some(Piece::Queen));
+    }
+
+    #[test]
+    fn incremental_score_matches_full_score() {
+        // castling on both sides, an en passant capture and a promotion with capture.
+        let board = Board::from_str("r3k2r/6P1/8/8/5p2/8/4P3/R3K2R w KQkq - 0 1").unwrap();
+        let mut state = BoardState::from_board(board);
+        for uci in ["e2e4", "f4e3", "e1g1", "e8c8", "g7h8q"] {
+            let m = ChessMove::from_str(uci).unwrap();
+            assert!(state.board.legal(m), "{} is illegal", uci);
+            state = state.apply_move(&m);
+            let full = BoardState::from_board(state.board);
+            assert_eq!(
+                state.acc.white_position, full.acc.white_position,
+                "after {}",
+                uci
+            );
+            assert_eq!(
+                state.acc.black_position, full.acc.black_position,
+                "after {}",
+                uci
+            );
+            assert_eq!(state.acc.phase, full.acc.phase, "after {}", uci);
+            assert_eq!(state.acc.pawn_key, full.acc.pawn_key, "after {}", uci);
+        }
+    }
+
+    #[test]
+    fn phase_follows_material() {
+        assert_eq!(board_phase(&Board::default()), MAX_PHASE);
+        let pawn_ending = Board::from_str("4k3/pppp4/8/8/8/8/4PPPP/4K3 w - - 0 1").unwrap();
+        assert_eq!(board_phase(&pawn_ending), 0);
     }
 
     #[test]
@@ -506,19 +1308,74 @@ mod test {
         let state = BoardState::from_board(board);
         let d = 1;
         let result = nega_max(state, NegaMaxOptions::new().depth(d));
-        assert_eq!(result.score, -CHECKMATE_SCORE);
+        assert_eq!(result.score, mate_in(1));
+        assert_eq!(mate_distance(result.score), Some(1));
+    }
+
+    #[test]
+    fn mate_in_one_with_tt() {
+        let board = Board::from_str("5k2/QR6/8/8/6K1/8/8/8 w - - 0 1").unwrap();
+        let tt = Arc::new(TranspositionTable::new(1));
+        let opts = NegaMaxOptions::new().depth(3).tt(tt.clone());
+        let result = nega_max(BoardState::from_board(board), opts.clone());
+        assert_eq!(result.score, mate_in(1));
+        // the root still has to search for a move, but every child is answered from the table.
+        let again = nega_max(BoardState::from_board(board), opts);
+        assert_eq!(again.score, result.score);
+        assert_eq!(again.pv[0], result.pv[0]);
+        assert!(again.nodes < result.nodes);
+        let entry = tt.probe(board.get_hash()).unwrap();
+        assert_eq!(entry.best_move, Some(result.pv[0]));
+    }
+
+    #[test]
+    fn principal_variation_ends_in_mate() {
+        let board = Board::from_str("r6k/4Rppp/8/8/8/8/8/4R2K w - - 0 1").unwrap();
+        let result = nega_max(
+            BoardState::from_board(board),
+            NegaMaxOptions::new().depth(4),
+        );
+        assert_eq!(result.pv.len(), 3);
+        let end = result.pv.iter().fold(board, |b, m| b.make_move_new(*m));
+        assert_eq!(end.status(), BoardStatus::Checkmate);
+    }
+
+    #[test]
+    fn quiescence_sees_recapture() {
+        // the queen can grab d5, but the e6 pawn recaptures.
+        let board = Board::from_str("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+        let state = BoardState::from_board(board);
+        let result = nega_max(state.clone(), NegaMaxOptions::new().depth(1));
+        assert!(result.score < state.board_score() + PAWN / 2);
+    }
+
+    #[test]
+    fn quiescence_limits() {
+        // white is in check, so every evasion is searched.
+        let board = Board::from_str("4k3/8/8/8/8/8/4r3/4K3 w - - 0 1").unwrap();
+        let state = BoardState::from_board(board);
+        let mut ctx = SearchContext::new();
+        let opts = NegaMaxOptions::new();
+        let deep = quiescence(&state, MAX_PLY, MIN_SCORE, -MIN_SCORE, &opts, &mut ctx);
+        assert!(deep.is_complete);
+        assert_eq!(deep.score, state.board_score());
+
+        let stopped = opts.signal(Arc::new(AtomicBool::new(true)));
+        let result = quiescence(&state, 0, MIN_SCORE, -MIN_SCORE, &stopped, &mut ctx);
+        assert!(!result.is_complete);
     }
 
     #[test]
     fn mate_in_two() {
         let board = Board::from_str("r6k/4Rppp/8/8/8/8/8/4R2K w - - 0 1").unwrap();
         let state = BoardState::from_board(board);
-        let d1 = 1;
-        let d2 = 4;
-        let result1 = nega_max(state.clone(), NegaMaxOptions::new().depth(d1));
-        let result2 = nega_max(state.clone(), NegaMaxOptions::new().depth(d2));
-        assert!(result1.score < result2.score);
-        assert_eq!(result2.score, -CHECKMATE_SCORE + 1); // mate in 2 should be slightly better than other mates
+        // the distance doesn't depend on how deep we searched past the mate (at depth 1 the
+        // quiescence search follows the captures all the way to it).
+        for d in [1, 4, 5, 6] {
+            let result = nega_max(state.clone(), NegaMaxOptions::new().depth(d));
+            assert_eq!(result.score, mate_in(3));
+            assert_eq!(mate_distance(result.score), Some(2));
+        }
     }
 
     #[test]
@@ -527,7 +1384,65 @@ mod test {
         let state = BoardState::from_board(board);
         let d = 3;
         let result = nega_max(state.clone(), NegaMaxOptions::new().depth(d));
-        assert_eq!(result.score, -CHECKMATE_SCORE + 2); // mate in 1 should be slightly better than mate in two
+        // mate in one is preferred over the mate in two.
+        assert_eq!(mate_distance(result.score), Some(1));
+    }
+
+    #[test]
+    fn checks_are_extended() {
+        // Qg8+ Rxg8 Nf7 is a smothered mate, but Nf7 is a quiet move two plies in.
+        let board = Board::from_str("5r1k/6pp/7N/8/2Q5/8/8/6K1 w - - 0 1").unwrap();
+        let opts = NegaMaxOptions::new().depth(1);
+        let plain = nega_max(BoardState::from_board(board), opts.extensions(false));
+        assert_eq!(mate_distance(plain.score), None);
+        // the check, the only reply and the mating check are all extended.
+        let result = nega_max(BoardState::from_board(board), opts);
+        assert_eq!(mate_distance(result.score), Some(2));
+        assert_eq!(result.pv[0], ChessMove::from_str("c4g8").unwrap());
+        let plain = nega_max(
+            BoardState::from_board(board),
+            NegaMaxOptions::new().depth(3).extensions(false),
+        );
+        assert_eq!(mate_distance(plain.score), Some(2));
+    }
+
+    #[test]
+    fn getting_mated() {
+        // black can only push the pawn, then Qh8 mates.
+        let board = Board::from_str("k7/8/1K6/p7/8/8/7Q/8 b - - 0 1").unwrap();
+        let result = nega_max(
+            BoardState::from_board(board),
+            NegaMaxOptions::new().depth(4),
+        );
+        assert_eq!(result.score, mated_in(2));
+        assert_eq!(mate_distance(result.score), Some(-1));
+    }
+
+    #[test]
+    fn mate_scores_through_the_table() {
+        let board = Board::from_str("r6k/4Rppp/8/8/8/8/8/4R2K w - - 0 1").unwrap();
+        let tt = Arc::new(TranspositionTable::new(1));
+        let opts = NegaMaxOptions::new().tt(tt);
+        for d in 1..=6 {
+            // later iterations find the mate stored by earlier ones, from other plies.
+            let result = nega_max(BoardState::from_board(board), opts.depth(d));
+            assert_eq!(mate_distance(result.score), Some(2));
+        }
+    }
+
+    #[test]
+    fn null_move() {
+        let board = Board::from_str("4k3/8/8/3p4/4P3/2N5/8/4K3 w - - 0 1").unwrap();
+        let state = BoardState::from_board(board);
+        let passed = state.apply_null_move().unwrap();
+        assert_eq!(passed.board.side_to_move(), Color::Black);
+        assert_eq!(passed.board_score(), -state.board_score());
+        assert!(has_non_pawn_material(&state.board));
+        // black only has a king and pawns, so passing is too risky there.
+        assert!(!has_non_pawn_material(&passed.board));
+
+        let in_check = Board::from_str("4k3/8/8/8/8/8/8/4K2r w - - 0 1").unwrap();
+        assert!(BoardState::from_board(in_check).apply_null_move().is_none());
     }
 
     #[test]
@@ -541,8 +1456,126 @@ mod test {
         let state_white = BoardState::from_board(board_for_white);
         let state_black = BoardState::from_board(same_board_for_black_but_reversed);
         let d = 3;
-        let result1 = nega_max(state_white, NegaMaxOptions::new().depth(d));
-        let result2 = nega_max(state_black, NegaMaxOptions::new().depth(d));
+        // which moves get reduced depends on the order they are generated in, and that isn't
+        // mirrored between the colors.
+        let opts = NegaMaxOptions::new()
+            .depth(d)
+            .lmr(Arc::new(ReductionTable::disabled()));
+        let result1 = nega_max(state_white, opts.clone());
+        let result2 = nega_max(state_black, opts);
         assert!(result1.score == result2.score);
     }
+
+    #[test]
+    fn search_window() {
+        let board = Board::from_str("4k3/8/8/8/8/8/3PP3/4K3 w - - 0 1").unwrap();
+        let opts = NegaMaxOptions::new().depth(3);
+        let exact = nega_max(BoardState::from_board(board), opts.clone()).score;
+        let high = nega_max(
+            BoardState::from_board(board),
+            opts.window(exact - 100, exact - 50),
+        );
+        assert!(high.score >= exact - 50);
+        let low = nega_max(
+            BoardState::from_board(board),
+            opts.window(exact + 50, exact + 100),
+        );
+        assert!(low.score <= exact + 50);
+        let inside = nega_max(
+            BoardState::from_board(board),
+            opts.window(exact - 50, exact + 50),
+        );
+        assert_eq!(inside.score, exact);
+    }
+
+    #[test]
+    fn fifty_move_rule() {
+        // every white move is quiet, so the next one ends the game in a draw.
+        let board = Board::from_str("4k3/8/8/8/8/8/8/Q3K3 w - - 0 1").unwrap();
+        let opts = NegaMaxOptions::new().depth(2);
+        let fresh = nega_max(BoardState::from_board(board), opts.clone());
+        assert!(fresh.score > 500);
+        let state = BoardState::from_board(board).halfmove_clock(99);
+        assert_eq!(nega_max(state, opts).score, DRAW_SCORE);
+    }
+
+    #[test]
+    fn repetition_saves_a_lost_game() {
+        // the kings shuffled back and forth, moving the white king back repeats the position.
+        let mut board = Board::from_str("7k/6rr/8/8/8/8/8/1K6 b - - 0 1").unwrap();
+        let mut history = GameHistory::new();
+        for m in ["h8g8", "b1a1", "g8h8"] {
+            let m = ChessMove::from_str(m).unwrap();
+            history.push(&board, m);
+            board = board.make_move_new(m);
+        }
+        let opts = NegaMaxOptions::new().depth(3);
+        let lost = nega_max(BoardState::from_board(board), opts.clone());
+        assert!(lost.score < -500);
+        let state = BoardState::from_board(board).halfmove_clock(history.halfmove_clock());
+        let saved = nega_max(state, opts.history(history));
+        assert_eq!(saved.score, DRAW_SCORE);
+        assert_eq!(saved.pv, vec![ChessMove::from_str("a1b1").unwrap()]);
+    }
+
+    #[test]
+    fn late_move_reductions() {
+        let board =
+            Board::from_str("r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N2N2/PP2BPPP/R2QKB1R w KQ - 0 1")
+                .unwrap();
+        let full = NegaMaxOptions::new()
+            .depth(5)
+            .lmr(Arc::new(ReductionTable::disabled()));
+        let reduced = nega_max(BoardState::from_board(board), full.lmr(Arc::default()));
+        let full = nega_max(BoardState::from_board(board), full);
+        assert!(reduced.nodes < full.nodes);
+        assert!((reduced.score - full.score).abs() < PAWN);
+    }
+
+    #[test]
+    fn excluded_root_moves() {
+        // the pawn and the rook can both take the queen, the pawn is the better way.
+        let board = Board::from_str("4k3/8/8/3q4/4P3/8/8/3RK3 w - - 0 1").unwrap();
+        let opts = NegaMaxOptions::new().depth(3);
+        let mut ctx = SearchContext::new();
+        let best = nega_max_with_context(BoardState::from_board(board), opts.clone(), &mut ctx);
+        assert_eq!(best.pv[0], ChessMove::from_str("e4d5").unwrap());
+        ctx.root_excluded.push(best.pv[0]);
+        let second = nega_max_with_context(BoardState::from_board(board), opts, &mut ctx);
+        assert_eq!(second.pv[0], ChessMove::from_str("d1d5").unwrap());
+        assert!(second.score < best.score);
+    }
+
+    #[test]
+    fn infinite_search_waits_for_the_signal() {
+        let signal = Arc::new(AtomicBool::new(false));
+        let opts = NegaMaxOptions::new().signal(signal.clone());
+        // a search with limits returns right away.
+        opts.wait_for_stop();
+        let stopper = {
+            let signal = signal.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(50));
+                signal.store(true, Ordering::Relaxed);
+            })
+        };
+        opts.infinite(true).wait_for_stop();
+        assert!(signal.load(Ordering::Relaxed));
+        stopper.join().unwrap();
+    }
+
+    #[test]
+    fn search_moves_and_node_budget() {
+        let board = Board::from_str("4k3/8/8/3q4/4P3/8/8/3RK3 w - - 0 1").unwrap();
+        let rook = ChessMove::from_str("d1d5").unwrap();
+        let opts = NegaMaxOptions::new().depth(3);
+        let only_rook = nega_max(BoardState::from_board(board), opts.search_moves(vec![rook]));
+        assert_eq!(only_rook.pv[0], rook);
+
+        let mut ctx = SearchContext::new();
+        let opts = opts.depth(20).nodes(500);
+        let cut = nega_max_with_context(BoardState::from_board(board), opts, &mut ctx);
+        assert!(!cut.is_complete);
+        assert!(ctx.nodes() >= 500 && ctx.nodes() < 1000);
+    }
 }
@@ -0,0 +1,157 @@
+use crate::eval_params::EvalParams;
+use crate::evaluation::{
+    board_phase, pawn_key_diff, score_board_material, score_board_position, score_capture_diff,
+    score_phase_diff, score_position_diff, MoveInfo,
+};
+use crate::king_safety::king_safety;
+use crate::mobility::{score_board_activity, EvalTerms};
+use crate::pawns::{pawn_key, pawn_structure};
+use crate::piece_table::Score;
+use chess::{Board, Color};
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// A static evaluation the search can be run with. An evaluator scores a board from white's
+/// point of view and may keep some state along with the board (e.g., running sums) that is
+/// updated move by move instead of being recomputed at every node.
+pub trait Evaluator: Send + Sync + 'static {
+    /// Whatever the evaluator wants to carry from one position to the next, `()` if nothing.
+    type State: Clone + Debug + Send + Sync;
+
+    /// Builds the state for a board from scratch.
+    fn init(&self, board: &Board) -> Self::State;
+
+    /// Brings `state` up to date after the move described by `info`, `board` is the position
+    /// after the move. By default the state is simply rebuilt.
+    fn update(&self, state: &mut Self::State, info: &MoveInfo, board: &Board) {
+        let _ = info;
+        *state = self.init(board);
+    }
+
+    /// Scores the board from white's point of view.
+    fn evaluate(&self, board: &Board, state: &Self::State) -> i32;
+}
+
+/// The running sums the piece-square evaluator keeps up to date as moves are made.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PstState {
+    pub white_position: Score,
+    pub black_position: Score,
+    // MAX_PHASE with all pieces on the board, down to 0 when only kings and pawns are left.
+    pub phase: i32,
+    // zobrist key of the pawns alone, indexes the pawn hash table.
+    pub pawn_key: u64,
+}
+
+/// The default evaluation: tapered piece-square tables updated incrementally, plus pawn
+/// structure, king safety and the piece activity terms enabled in `terms`.
+#[derive(Clone, Debug, Default)]
+pub struct PstEvaluator {
+    terms: EvalTerms,
+    params: Arc<EvalParams>,
+}
+
+impl PstEvaluator {
+    pub fn new() -> Self {
+        return Self::with_terms(EvalTerms::default());
+    }
+
+    pub fn with_terms(terms: EvalTerms) -> Self {
+        return Self {
+            terms,
+            params: Arc::new(EvalParams::default()),
+        };
+    }
+
+    /// Use `params` instead of the built-in piece values and tables.
+    pub fn params(mut self, params: EvalParams) -> Self {
+        self.params = Arc::new(params);
+        return self;
+    }
+
+    pub fn get_terms(&self) -> EvalTerms {
+        return self.terms;
+    }
+
+    pub fn get_params(&self) -> &EvalParams {
+        return &self.params;
+    }
+}
+
+impl Evaluator for PstEvaluator {
+    type State = PstState;
+
+    fn init(&self, board: &Board) -> PstState {
+        let (white_position, black_position) = score_board_position(board, &self.params);
+        return PstState {
+            white_position,
+            black_position,
+            phase: board_phase(board),
+            pawn_key: pawn_key(board),
+        };
+    }
+
+    fn update(&self, state: &mut PstState, info: &MoveInfo, _board: &Board) {
+        let position_diff = score_position_diff(info, &self.params);
+        let capture_diff = score_capture_diff(info, &self.params);
+        if info.color_to_move == Color::White {
+            state.white_position += position_diff;
+            state.black_position += capture_diff;
+        } else {
+            state.black_position += position_diff;
+            state.white_position += capture_diff;
+        }
+        state.phase += score_phase_diff(info);
+        state.pawn_key ^= pawn_key_diff(info);
+    }
+
+    fn evaluate(&self, board: &Board, state: &PstState) -> i32 {
+        let pawns = pawn_structure(board, state.pawn_key);
+        let king = king_safety(board);
+        let activity = score_board_activity(board, &self.terms);
+        let score = state.white_position - state.black_position + pawns + king + activity;
+        return score.taper(state.phase);
+    }
+}
+
+/// Counts material and nothing else. Mostly useful as a baseline to test other evaluations against.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MaterialEvaluator;
+
+impl Evaluator for MaterialEvaluator {
+    type State = ();
+
+    fn init(&self, _board: &Board) {}
+
+    fn evaluate(&self, board: &Board, _state: &()) -> i32 {
+        let (white, black) = score_board_material(board);
+        return white - black;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::evaluation::{mate_in, nega_max, BoardState, NegaMaxOptions};
+    use crate::piece_table::PAWN;
+    use chess::ChessMove;
+    use std::str::FromStr;
+    use std::sync::Arc;
+
+    #[test]
+    fn material_evaluator() {
+        let board = Board::from_str("4k3/8/8/8/8/8/PP6/4K3 b - - 0 1").unwrap();
+        assert_eq!(MaterialEvaluator.evaluate(&board, &()), 2 * PAWN);
+        let state = BoardState::with_evaluator(board, Arc::new(MaterialEvaluator));
+        assert_eq!(state.board_score(), -2 * PAWN);
+    }
+
+    #[test]
+    fn search_with_another_evaluator() {
+        let board = Board::from_str("k7/8/2K5/8/8/8/8/1Q6 w - - 0 1").unwrap();
+        let state = BoardState::with_evaluator(board, Arc::new(MaterialEvaluator));
+        let result = nega_max(state, NegaMaxOptions::new().depth(1));
+        assert_eq!(result.score, mate_in(1));
+        assert_eq!(result.pv, vec![ChessMove::from_str("b1b7").unwrap()]);
+    }
+}
@@ -0,0 +1,77 @@
+use chess::{Board, ChessMove, Piece};
+
+/// The positions played before the one being searched, so the search can tell when a line
+/// repeats one of them or runs into the fifty-move rule.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GameHistory {
+    // hashes of the positions since the last capture or pawn move, oldest first. nothing
+    // before that can ever come back.
+    hashes: Vec<u64>,
+    // plies since the last capture or pawn move, which reaches further back than `hashes`
+    // when the game started from a fen.
+    halfmove_clock: u32,
+}
+
+impl GameHistory {
+    pub fn new() -> Self {
+        return Self::with_halfmove_clock(0);
+    }
+
+    /// A history that starts at a position `halfmove_clock` plies after the last capture or
+    /// pawn move (the fifth field of a fen).
+    pub fn with_halfmove_clock(halfmove_clock: u32) -> Self {
+        return Self {
+            hashes: Vec::new(),
+            halfmove_clock,
+        };
+    }
+
+    /// Records `board` as played before `m` is made on it.
+    pub fn push(&mut self, board: &Board, m: ChessMove) {
+        if is_irreversible(board, m) {
+            self.hashes.clear();
+            self.halfmove_clock = 0;
+        } else {
+            self.hashes.push(board.get_hash());
+            self.halfmove_clock += 1;
+        }
+    }
+
+    pub fn hashes(&self) -> &[u64] {
+        return &self.hashes;
+    }
+
+    pub fn halfmove_clock(&self) -> u32 {
+        return self.halfmove_clock;
+    }
+}
+
+/// Whether no position before `m` can occur again after it, i.e. it is a capture or a pawn move.
+pub fn is_irreversible(board: &Board, m: ChessMove) -> bool {
+    return board.piece_on(m.get_source()) == Some(Piece::Pawn)
+        || board.piece_on(m.get_dest()).is_some();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn irreversible_moves_reset_the_history() {
+        let mut history = GameHistory::new();
+        let mut board = Board::default();
+        for m in ["g1f3", "g8f6", "f3g1", "e7e5", "b1c3"] {
+            let m = ChessMove::from_str(m).unwrap();
+            history.push(&board, m);
+            board = board.make_move_new(m);
+        }
+        // only the position before the knight move is left after the pawn move.
+        assert_eq!(history.hashes().len(), 1);
+        assert_eq!(history.halfmove_clock(), 1);
+
+        let history = GameHistory::with_halfmove_clock(40);
+        assert!(history.hashes().is_empty());
+        assert_eq!(history.halfmove_clock(), 40);
+    }
+}
@@ -0,0 +1,150 @@
+use crate::piece_table::Score;
+use chess::{
+    get_adjacent_files, get_bishop_moves, get_file, get_king_moves, get_knight_moves, get_rank,
+    get_rook_moves, BitBoard, Board, Color, Piece, Rank, Square, ALL_COLORS, EMPTY,
+};
+
+// a pawn right in front of the king (or diagonally in front) and one a square further away.
+pub const SHIELD_PAWN_NEAR: i32 = 12;
+pub const SHIELD_PAWN_FAR: i32 = 6;
+// files around the king without any of our pawns, semi-open files still have an enemy pawn.
+pub const OPEN_FILE_NEAR_KING: i32 = -20;
+pub const SEMI_OPEN_FILE_NEAR_KING: i32 = -10;
+// weight of each piece attacking the king zone, the penalty grows with the square of the total.
+pub const KNIGHT_ATTACK_WEIGHT: i32 = 2;
+pub const BISHOP_ATTACK_WEIGHT: i32 = 2;
+pub const ROOK_ATTACK_WEIGHT: i32 = 3;
+pub const QUEEN_ATTACK_WEIGHT: i32 = 5;
+pub const MAX_ATTACK_PENALTY: i32 = 400;
+
+/// Scores the safety of both kings from white's point of view. King safety only matters while
+/// there are pieces around to attack, so the terms are middlegame only.
+pub fn king_safety(board: &Board) -> Score {
+    let mut score = 0;
+    for color in ALL_COLORS {
+        let side = pawn_shield(board, color) + open_files(board, color) + king_attack(board, color);
+        score += if color == Color::White { side } else { -side };
+    }
+    return Score::new(score, 0);
+}
+
+/// Bonus for our pawns standing on the three files around the king, one or two ranks ahead.
+pub fn pawn_shield(board: &Board, color: Color) -> i32 {
+    let king = board.king_square(color);
+    let pawns = *board.pieces(Piece::Pawn) & *board.color_combined(color);
+    let files = king_files(king);
+    let near = ranks_ahead(king, color, 1).map_or(EMPTY, |r| get_rank(r) & files);
+    let far = ranks_ahead(king, color, 2).map_or(EMPTY, |r| get_rank(r) & files);
+    return (pawns & near).popcnt() as i32 * SHIELD_PAWN_NEAR
+        + (pawns & far).popcnt() as i32 * SHIELD_PAWN_FAR;
+}
+
+/// Penalty for files next to the king that our pawns don't cover.
+pub fn open_files(board: &Board, color: Color) -> i32 {
+    let king = board.king_square(color);
+    let pawns = *board.pieces(Piece::Pawn);
+    let ours = pawns & *board.color_combined(color);
+    let mut score = 0;
+    for sq in get_rank(king.get_rank()) & king_files(king) {
+        let file = get_file(sq.get_file());
+        if file & ours != EMPTY {
+            continue;
+        }
+        score += if file & pawns == EMPTY {
+            OPEN_FILE_NEAR_KING
+        } else {
+            SEMI_OPEN_FILE_NEAR_KING
+        };
+    }
+    return score;
+}
+
+/// Penalty for enemy pieces attacking the squares around the king. A lone attacker rarely
+/// gets anywhere, so it takes at least two before the penalty kicks in.
+pub fn king_attack(board: &Board, color: Color) -> i32 {
+    let king = board.king_square(color);
+    let zone = get_king_moves(king) | BitBoard::from_square(king);
+    let occupied = *board.combined();
+    let theirs = *board.color_combined(!color);
+
+    let mut attackers = 0;
+    let mut weight = 0;
+    for (piece, piece_weight) in [
+        (Piece::Knight, KNIGHT_ATTACK_WEIGHT),
+        (Piece::Bishop, BISHOP_ATTACK_WEIGHT),
+        (Piece::Rook, ROOK_ATTACK_WEIGHT),
+        (Piece::Queen, QUEEN_ATTACK_WEIGHT),
+    ] {
+        for sq in *board.pieces(piece) & theirs {
+            let attacks = match piece {
+                Piece::Knight => get_knight_moves(sq),
+                Piece::Bishop => get_bishop_moves(sq, occupied),
+                Piece::Rook => get_rook_moves(sq, occupied),
+                _ => get_bishop_moves(sq, occupied) | get_rook_moves(sq, occupied),
+            };
+            if attacks & zone != EMPTY {
+                attackers += 1;
+                weight += piece_weight;
+            }
+        }
+    }
+    if attackers < 2 {
+        return 0;
+    }
+    return -(weight * weight * 2).min(MAX_ATTACK_PENALTY);
+}
+
+fn king_files(king: Square) -> BitBoard {
+    return get_file(king.get_file()) | get_adjacent_files(king.get_file());
+}
+
+fn ranks_ahead(king: Square, color: Color, distance: usize) -> Option<Rank> {
+    let rank = king.get_rank().to_index();
+    let target = match color {
+        Color::White => rank + distance,
+        Color::Black => rank.checked_sub(distance)?,
+    };
+    if target > 7 {
+        return None;
+    }
+    return Some(Rank::from_index(target));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn start_position_is_even() {
+        assert_eq!(king_safety(&Board::default()), Score::default());
+        assert_eq!(
+            pawn_shield(&Board::default(), Color::White),
+            3 * SHIELD_PAWN_NEAR
+        );
+    }
+
+    #[test]
+    fn pushed_pawns_weaken_the_king() {
+        let castled = Board::from_str("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1").unwrap();
+        let pushed = Board::from_str("6k1/5ppp/8/8/8/6PP/5P2/6K1 w - - 0 1").unwrap();
+        let open = Board::from_str("6k1/5ppp/8/8/8/8/5P1P/6K1 w - - 0 1").unwrap();
+        assert_eq!(king_safety(&castled), Score::default());
+        assert!(king_safety(&pushed).mg < 0);
+        assert!(king_safety(&open).mg < king_safety(&pushed).mg);
+        assert_eq!(open_files(&open, Color::White), SEMI_OPEN_FILE_NEAR_KING);
+    }
+
+    #[test]
+    fn attackers_on_the_king_zone() {
+        // queen and knight both hit the squares around the white king.
+        let attacked = Board::from_str("6k1/8/8/8/8/5n2/5PPq/6K1 w - - 0 1").unwrap();
+        let weight = QUEEN_ATTACK_WEIGHT + KNIGHT_ATTACK_WEIGHT;
+        assert_eq!(king_attack(&attacked, Color::White), -weight * weight * 2);
+        assert_eq!(king_attack(&attacked, Color::Black), 0);
+
+        // a single attacker isn't enough.
+        let lone = Board::from_str("6k1/8/8/8/8/8/5PPq/6K1 w - - 0 1").unwrap();
+        assert_eq!(king_attack(&lone, Color::White), 0);
+    }
+}
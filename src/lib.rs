@@ -1,5 +1,23 @@
+#![allow(clippy::needless_return)]
+
+pub mod bench;
 pub mod engine;
+pub mod eval_params;
 pub mod evaluation;
+pub mod evaluator;
+pub mod game_history;
+pub mod king_safety;
+pub mod mobility;
+pub mod move_order;
+pub mod pawns;
 pub mod pgn;
 pub mod piece_table;
+pub mod pruning;
+pub mod pv;
+pub mod reductions;
+pub mod see;
+pub mod time_manager;
+pub mod transposition;
+pub mod tuner;
 pub mod uci;
+pub mod worker;
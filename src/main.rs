@@ -1,4 +1,8 @@
+#![allow(clippy::needless_return)]
+
+use rust_engine::bench::{self, BenchResult, DEFAULT_BENCH_DEPTH, TACTICS};
 use rust_engine::engine::get_engine;
+use rust_engine::pruning::PruningParams;
 use rust_engine::uci::{UCIEngine, UCITestEngine};
 
 fn main() {
@@ -15,6 +19,15 @@ fn main() {
         }
         return;
     }
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(|a| a == "bench").unwrap_or(false) {
+        let depth = args
+            .get(2)
+            .and_then(|d| d.parse::<i8>().ok())
+            .unwrap_or(DEFAULT_BENCH_DEPTH);
+        run_bench(depth);
+        return;
+    }
     let mut engine = UCIEngine::new(get_engine);
 
     if let Err(e) = engine.run() {
@@ -22,6 +35,30 @@ fn main() {
     }
 }
 
+/// Searches the bench positions with and without pruning and reports what pruning saves.
+fn run_bench(depth: i8) {
+    let print = |name: &str, result: &BenchResult| {
+        println!(
+            "{:<12} nodes {:>10} time {:>6} ms nps {:>8} tactics {}/{}",
+            name,
+            result.nodes,
+            result.elapsed.as_millis(),
+            result.nps(),
+            result.solved,
+            TACTICS.len()
+        );
+    };
+    let unpruned = bench::run(depth, PruningParams::disabled());
+    print("no pruning", &unpruned);
+    let pruned = bench::run(depth, PruningParams::new());
+    print("pruning", &pruned);
+    let saved = 100.0 - pruned.nodes as f64 * 100.0 / unpruned.nodes.max(1) as f64;
+    println!(
+        "pruning searched {:.1}% fewer nodes at depth {}",
+        saved, depth
+    );
+}
+
 struct CommandLineOptions {
     pub eng1: String,
     pub eng2: String,
@@ -0,0 +1,213 @@
+use crate::piece_table::Score;
+use chess::{
+    get_bishop_moves, get_file, get_knight_moves, get_pawn_attacks, get_rank, get_rook_moves,
+    BitBoard, Board, Color, Piece, Rank, ALL_COLORS, EMPTY,
+};
+
+// per square bonus for each attacked square above (or penalty below) a typical count.
+pub const KNIGHT_MOBILITY: Score = Score { mg: 4, eg: 4 };
+pub const BISHOP_MOBILITY: Score = Score { mg: 5, eg: 5 };
+pub const ROOK_MOBILITY: Score = Score { mg: 2, eg: 4 };
+pub const QUEEN_MOBILITY: Score = Score { mg: 1, eg: 2 };
+const KNIGHT_BASELINE: i32 = 4;
+const BISHOP_BASELINE: i32 = 6;
+const ROOK_BASELINE: i32 = 7;
+const QUEEN_BASELINE: i32 = 13;
+
+pub const BISHOP_PAIR: Score = Score { mg: 30, eg: 50 };
+pub const ROOK_OPEN_FILE: Score = Score { mg: 20, eg: 10 };
+pub const ROOK_SEMI_OPEN_FILE: Score = Score { mg: 10, eg: 5 };
+pub const ROOK_ON_SEVENTH: Score = Score { mg: 20, eg: 30 };
+// b1, d1, ..., a2, c2, ...
+const LIGHT_SQUARES: BitBoard = BitBoard(0x55AA_55AA_55AA_55AA);
+
+/// Which piece activity terms take part in the evaluation, each can be switched off on its
+/// own to measure what it is worth.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EvalTerms {
+    pub mobility: bool,
+    pub bishop_pair: bool,
+    pub rook_open_file: bool,
+    pub rook_seventh: bool,
+}
+
+impl EvalTerms {
+    pub fn new() -> Self {
+        return Self {
+            mobility: true,
+            bishop_pair: true,
+            rook_open_file: true,
+            rook_seventh: true,
+        };
+    }
+}
+
+impl Default for EvalTerms {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+/// Scores the activity of the pieces from white's point of view.
+pub fn score_board_activity(board: &Board, terms: &EvalTerms) -> Score {
+    let mut score = Score::default();
+    for color in ALL_COLORS {
+        let mut side = Score::default();
+        if terms.mobility {
+            side += mobility(board, color);
+        }
+        if terms.bishop_pair && has_bishop_pair(board, color) {
+            side += BISHOP_PAIR;
+        }
+        if terms.rook_open_file {
+            side += rook_files(board, color);
+        }
+        if terms.rook_seventh {
+            side += rook_seventh(board, color);
+        }
+        score += if color == Color::White { side } else { -side };
+    }
+    return score;
+}
+
+/// Counts the squares each piece attacks that are neither ours nor covered by an enemy pawn.
+pub fn mobility(board: &Board, color: Color) -> Score {
+    let occupied = *board.combined();
+    let theirs = *board.color_combined(!color);
+    let their_pawns = *board.pieces(Piece::Pawn) & theirs;
+    let mut pawn_attacks = EMPTY;
+    for sq in their_pawns {
+        pawn_attacks |= get_pawn_attacks(sq, !color, !EMPTY);
+    }
+    let available = !*board.color_combined(color) & !pawn_attacks;
+
+    let mut score = Score::default();
+    for (piece, weight, baseline) in [
+        (Piece::Knight, KNIGHT_MOBILITY, KNIGHT_BASELINE),
+        (Piece::Bishop, BISHOP_MOBILITY, BISHOP_BASELINE),
+        (Piece::Rook, ROOK_MOBILITY, ROOK_BASELINE),
+        (Piece::Queen, QUEEN_MOBILITY, QUEEN_BASELINE),
+    ] {
+        for sq in *board.pieces(piece) & *board.color_combined(color) {
+            let attacks = match piece {
+                Piece::Knight => get_knight_moves(sq),
+                Piece::Bishop => get_bishop_moves(sq, occupied),
+                Piece::Rook => get_rook_moves(sq, occupied),
+                _ => get_bishop_moves(sq, occupied) | get_rook_moves(sq, occupied),
+            };
+            let count = (attacks & available).popcnt() as i32 - baseline;
+            score += Score::new(weight.mg * count, weight.eg * count);
+        }
+    }
+    return score;
+}
+
+/// Bonus for rooks on files without our own pawns, more so when there are no pawns at all.
+pub fn rook_files(board: &Board, color: Color) -> Score {
+    let pawns = *board.pieces(Piece::Pawn);
+    let ours = pawns & *board.color_combined(color);
+    let mut score = Score::default();
+    for sq in *board.pieces(Piece::Rook) & *board.color_combined(color) {
+        let file = get_file(sq.get_file());
+        if file & pawns == EMPTY {
+            score += ROOK_OPEN_FILE;
+        } else if file & ours == EMPTY {
+            score += ROOK_SEMI_OPEN_FILE;
+        }
+    }
+    return score;
+}
+
+/// Bonus for rooks on the seventh rank, as long as there are pawns to eat there or the enemy
+/// king is stuck behind them.
+pub fn rook_seventh(board: &Board, color: Color) -> Score {
+    let (seventh, eighth) = match color {
+        Color::White => (Rank::Seventh, Rank::Eighth),
+        Color::Black => (Rank::Second, Rank::First),
+    };
+    let their_pawns = *board.pieces(Piece::Pawn) & *board.color_combined(!color);
+    let king_trapped = board.king_square(!color).get_rank() == eighth;
+    if !king_trapped && their_pawns & get_rank(seventh) == EMPTY {
+        return Score::default();
+    }
+    let rooks = *board.pieces(Piece::Rook) & *board.color_combined(color) & get_rank(seventh);
+    let count = rooks.popcnt() as i32;
+    return Score::new(ROOK_ON_SEVENTH.mg * count, ROOK_ON_SEVENTH.eg * count);
+}
+
+/// Whether `color` has bishops on both square colors, two bishops on the same color (after an
+/// underpromotion) don't cover each other's weaknesses.
+fn has_bishop_pair(board: &Board, color: Color) -> bool {
+    let bishops = *board.pieces(Piece::Bishop) & *board.color_combined(color);
+    return bishops & LIGHT_SQUARES != EMPTY && bishops & !LIGHT_SQUARES != EMPTY;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    const NONE: EvalTerms = EvalTerms {
+        mobility: false,
+        bishop_pair: false,
+        rook_open_file: false,
+        rook_seventh: false,
+    };
+
+    #[test]
+    fn start_position_is_even() {
+        let board = Board::default();
+        assert_eq!(
+            score_board_activity(&board, &EvalTerms::new()),
+            Score::default()
+        );
+        // undeveloped pieces have less room than usual.
+        assert!(mobility(&board, Color::White).mg < 0);
+    }
+
+    #[test]
+    fn terms_can_be_disabled() {
+        // white has the bishop pair and a rook on the open seventh, black has neither.
+        let board = Board::from_str("6k1/R4ppp/8/8/8/8/5PPP/2B2BK1 w - - 0 1").unwrap();
+        assert_eq!(score_board_activity(&board, &NONE), Score::default());
+        let pair = EvalTerms {
+            bishop_pair: true,
+            ..NONE
+        };
+        assert_eq!(score_board_activity(&board, &pair), BISHOP_PAIR);
+        let files = EvalTerms {
+            rook_open_file: true,
+            ..NONE
+        };
+        assert_eq!(score_board_activity(&board, &files), ROOK_OPEN_FILE);
+        let seventh = EvalTerms {
+            rook_seventh: true,
+            ..NONE
+        };
+        assert_eq!(score_board_activity(&board, &seventh), ROOK_ON_SEVENTH);
+        let mobile = EvalTerms {
+            mobility: true,
+            ..NONE
+        };
+        assert_eq!(
+            score_board_activity(&board, &mobile),
+            mobility(&board, Color::White) - mobility(&board, Color::Black)
+        );
+    }
+
+    #[test]
+    fn bishop_pair_needs_both_colors() {
+        let pair = Board::from_str("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1").unwrap();
+        assert!(has_bishop_pair(&pair, Color::White));
+        // c1 and e3 are both dark squares.
+        let same = Board::from_str("4k3/8/8/8/8/4B3/8/2B1K3 w - - 0 1").unwrap();
+        assert!(!has_bishop_pair(&same, Color::White));
+    }
+
+    #[test]
+    fn semi_open_file() {
+        let board = Board::from_str("3r2k1/3p1ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1").unwrap();
+        assert_eq!(rook_files(&board, Color::White), ROOK_SEMI_OPEN_FILE);
+        assert_eq!(rook_files(&board, Color::Black), Score::default());
+    }
+}
@@ -0,0 +1,206 @@
+use crate::evaluation::MoveInfo;
+use crate::piece_table::{piece_value, QUEEN};
+use crate::see::loses_material;
+use chess::{Board, ChessMove, MoveGen, Piece};
+use std::cmp::Reverse;
+
+pub const MAX_PLY: usize = 128;
+
+// ordering tiers, the hash move always goes first, then captures and promotions,
+// then the killers, quiet moves sorted by history and finally captures that lose material.
+const TT_MOVE_SCORE: i32 = 1_000_000;
+const TACTICAL_SCORE: i32 = 100_000;
+const LOSING_TACTICAL_SCORE: i32 = -100_000;
+const FIRST_KILLER_SCORE: i32 = 90_000;
+const SECOND_KILLER_SCORE: i32 = 80_000;
+// history scores are halved once any entry passes this, so quiets never outrank killers.
+const HISTORY_LIMIT: i32 = 50_000;
+
+/// Most valuable victim, least valuable attacker. Taking a queen with a pawn is tried
+/// before taking a pawn with a queen.
+pub fn mvv_lva(victim: Piece, attacker: Piece) -> i32 {
+    // a king capture is always safe (it would be illegal otherwise) so it never sorts last.
+    let attacker_value = piece_value(attacker).min(QUEEN);
+    return piece_value(victim) * 10 - attacker_value;
+}
+
+/// Scores a capture or promotion by the material it changes hands.
+pub fn score_tactical(info: &MoveInfo) -> i32 {
+    let capture = info
+        .move_events
+        .capture
+        .as_ref()
+        .map(|c| mvv_lva(c.piece, info.piece))
+        .unwrap_or(0);
+    let promotion = info
+        .move_events
+        .promotion
+        .as_ref()
+        .map(|p| piece_value(p.piece))
+        .unwrap_or(0);
+    return capture + promotion;
+}
+
+/// Returns only the captures and promotions in the position, best victims first.
+pub fn ordered_tactical_moves(board: &Board) -> Vec<ChessMove> {
+    let mut scored: Vec<(i32, ChessMove)> = MoveGen::new_legal(board)
+        .filter_map(|m| {
+            let info = MoveInfo::from_move(&m, board);
+            if info.is_tactical() {
+                Some((score_tactical(&info), m))
+            } else {
+                None
+            }
+        })
+        .collect();
+    scored.sort_by_key(|(score, _)| Reverse(*score));
+    return scored.into_iter().map(|(_, m)| m).collect();
+}
+
+/// Keeps the killer moves (quiet moves that caused a cutoff at the same ply) and the butterfly
+/// history table (cutoffs indexed by from/to square). Both are kept between iterative deepening
+/// iterations so each new iteration starts with what the previous ones learned.
+#[derive(Clone)]
+pub struct MoveOrderer {
+    killers: Vec<[Option<ChessMove>; 2]>,
+    history: Box<[[i32; 64]; 64]>,
+}
+
+impl MoveOrderer {
+    pub fn new() -> Self {
+        return Self {
+            killers: vec![[None, None]; MAX_PLY],
+            history: Box::new([[0; 64]; 64]),
+        };
+    }
+
+    /// Called between iterations so that older history counts slowly lose their weight.
+    pub fn age(&mut self) {
+        self.history
+            .iter_mut()
+            .for_each(|row| row.iter_mut().for_each(|h| *h /= 2));
+    }
+
+    /// Returns all legal moves in the order they should be searched.
+    pub fn ordered_moves(
+        &self,
+        board: &Board,
+        tt_move: Option<ChessMove>,
+        ply: usize,
+    ) -> Vec<ChessMove> {
+        let mut scored: Vec<(i32, ChessMove)> = MoveGen::new_legal(board)
+            .map(|m| (self.score_move(board, m, tt_move, ply), m))
+            .collect();
+        scored.sort_by_key(|(score, _)| Reverse(*score));
+        return scored.into_iter().map(|(_, m)| m).collect();
+    }
+
+    pub fn score_move(
+        &self,
+        board: &Board,
+        m: ChessMove,
+        tt_move: Option<ChessMove>,
+        ply: usize,
+    ) -> i32 {
+        if tt_move == Some(m) {
+            return TT_MOVE_SCORE;
+        }
+        let info = MoveInfo::from_move(&m, board);
+        if info.is_tactical() {
+            if loses_material(board, m) {
+                return LOSING_TACTICAL_SCORE + score_tactical(&info);
+            }
+            return TACTICAL_SCORE + score_tactical(&info);
+        }
+        let [first, second] = self.killers_at(ply);
+        if first == Some(m) {
+            return FIRST_KILLER_SCORE;
+        }
+        if second == Some(m) {
+            return SECOND_KILLER_SCORE;
+        }
+        return self.history_score(m);
+    }
+
+    pub fn killers_at(&self, ply: usize) -> [Option<ChessMove>; 2] {
+        return self.killers.get(ply).copied().unwrap_or([None, None]);
+    }
+
+    pub fn history_score(&self, m: ChessMove) -> i32 {
+        return self.history[m.get_source().to_index()][m.get_dest().to_index()];
+    }
+
+    /// Record a quiet move that caused a beta cutoff.
+    pub fn record_cutoff(&mut self, m: ChessMove, depth: i8, ply: usize) {
+        if let Some(killers) = self.killers.get_mut(ply) {
+            if killers[0] != Some(m) {
+                killers[1] = killers[0];
+                killers[0] = Some(m);
+            }
+        }
+        let depth = depth.max(0) as i32;
+        let entry = &mut self.history[m.get_source().to_index()][m.get_dest().to_index()];
+        *entry += depth * depth;
+        if *entry > HISTORY_LIMIT {
+            self.age();
+        }
+    }
+}
+
+impl Default for MoveOrderer {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn mvv_lva_prefers_big_victims_and_small_attackers() {
+        assert!(mvv_lva(Piece::Queen, Piece::Pawn) > mvv_lva(Piece::Queen, Piece::Rook));
+        assert!(mvv_lva(Piece::Queen, Piece::Queen) > mvv_lva(Piece::Rook, Piece::Pawn));
+        assert!(mvv_lva(Piece::Pawn, Piece::King) > 0);
+    }
+
+    #[test]
+    fn ordering_tiers() {
+        // white can take the queen with the pawn or the rook, or play quiet moves.
+        let board = Board::from_str("4k3/8/8/3q4/4P3/8/8/3RK3 w - - 0 1").unwrap();
+        let quiet = ChessMove::from_san(&board, "Kf2").unwrap();
+        let other_quiet = ChessMove::from_san(&board, "Rc1").unwrap();
+        let mut orderer = MoveOrderer::new();
+        orderer.record_cutoff(other_quiet, 4, 3);
+        orderer.record_cutoff(quiet, 4, 2);
+
+        let moves = orderer.ordered_moves(&board, Some(quiet), 2);
+        assert_eq!(moves[0], quiet);
+        assert_eq!(moves[1], ChessMove::from_san(&board, "exd5").unwrap());
+        assert_eq!(moves[2], ChessMove::from_san(&board, "Rxd5").unwrap());
+
+        // without a hash move the killer for this ply follows the captures.
+        let moves = orderer.ordered_moves(&board, None, 3);
+        assert_eq!(moves[2], other_quiet);
+    }
+
+    #[test]
+    fn losing_captures_go_last() {
+        // the queen can take a pawn defended by another pawn.
+        let board = Board::from_str("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+        let moves = MoveOrderer::new().ordered_moves(&board, None, 0);
+        assert_eq!(
+            *moves.last().unwrap(),
+            ChessMove::from_san(&board, "Qxd5").unwrap()
+        );
+    }
+
+    #[test]
+    fn tactical_moves_only() {
+        let board = Board::from_str("4k3/8/8/3q4/4P3/8/8/3RK3 w - - 0 1").unwrap();
+        let moves = ordered_tactical_moves(&board);
+        assert_eq!(moves.len(), 2);
+        assert_eq!(moves[0], ChessMove::from_san(&board, "exd5").unwrap());
+    }
+}
@@ -0,0 +1,266 @@
+use crate::piece_table::Score;
+use chess::{get_adjacent_files, get_file, Board, Color, Piece, Square, ALL_COLORS};
+use std::cell::RefCell;
+
+// penalties for weak pawns and bonuses for passed pawns (by rank relative to the pawn's side).
+pub const DOUBLED_PAWN: Score = Score { mg: -10, eg: -20 };
+pub const ISOLATED_PAWN: Score = Score { mg: -10, eg: -15 };
+pub const BACKWARD_PAWN: Score = Score { mg: -8, eg: -10 };
+pub const PASSED_PAWN: [Score; 8] = [
+    Score { mg: 0, eg: 0 },
+    Score { mg: 5, eg: 10 },
+    Score { mg: 10, eg: 15 },
+    Score { mg: 15, eg: 25 },
+    Score { mg: 25, eg: 45 },
+    Score { mg: 40, eg: 75 },
+    Score { mg: 60, eg: 120 },
+    Score { mg: 0, eg: 0 },
+];
+// extra endgame bonus for a passed pawn with nothing in front of it on the way to promotion.
+pub const PASSED_PAWN_FREE_PATH: [i32; 8] = [0, 0, 5, 10, 20, 35, 60, 0];
+
+const PAWN_TABLE_ENTRIES: usize = 1 << 14;
+
+/// Zobrist keys for a pawn of each color on each square, used to build a hash of the pawn
+/// structure alone (the chess crate doesn't implement one).
+pub const PAWN_ZOBRIST: [[u64; 64]; 2] = pawn_zobrist_keys();
+
+const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    return (state, z ^ (z >> 31));
+}
+
+const fn pawn_zobrist_keys() -> [[u64; 64]; 2] {
+    let mut keys = [[0; 64]; 2];
+    let mut state = 0x5eed_0f9a_u64;
+    let mut color = 0;
+    while color < 2 {
+        let mut sq = 0;
+        while sq < 64 {
+            let (next, key) = splitmix64(state);
+            state = next;
+            keys[color][sq] = key;
+            sq += 1;
+        }
+        color += 1;
+    }
+    return keys;
+}
+
+pub fn pawn_zobrist(color: Color, sq: Square) -> u64 {
+    return PAWN_ZOBRIST[color.to_index()][sq.to_index()];
+}
+
+/// Computes the pawn key of a board from scratch.
+pub fn pawn_key(board: &Board) -> u64 {
+    let mut key = 0;
+    for color in ALL_COLORS {
+        for sq in *board.pieces(Piece::Pawn) & *board.color_combined(color) {
+            key ^= pawn_zobrist(color, sq);
+        }
+    }
+    return key;
+}
+
+/// The cached, pawn-only part of the evaluation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PawnEntry {
+    pub key: u64,
+    // from white's point of view.
+    pub score: Score,
+    // passed pawns of each color, the free path bonus depends on the other pieces so it is added later.
+    pub passed: [u64; 2],
+}
+
+/// A small always-replace cache of pawn structure evaluations.
+pub struct PawnHashTable {
+    entries: Vec<Option<PawnEntry>>,
+}
+
+impl PawnHashTable {
+    pub fn new(entries: usize) -> Self {
+        let len = entries.max(1).next_power_of_two();
+        return Self {
+            entries: vec![None; len],
+        };
+    }
+
+    pub fn probe(&self, key: u64) -> Option<PawnEntry> {
+        let entry = self.entries[key as usize & (self.entries.len() - 1)];
+        return entry.filter(|e| e.key == key);
+    }
+
+    pub fn store(&mut self, entry: PawnEntry) {
+        let idx = entry.key as usize & (self.entries.len() - 1);
+        self.entries[idx] = Some(entry);
+    }
+}
+
+thread_local! {
+    // one table per search thread, so probing never needs a lock.
+    static PAWN_TABLE: RefCell<PawnHashTable> = RefCell::new(PawnHashTable::new(PAWN_TABLE_ENTRIES));
+}
+
+/// Scores the pawn structure of the board from white's point of view, `key` must be the pawn key
+/// of the board. The pawn-only terms come from this thread's pawn hash table when possible.
+pub fn pawn_structure(board: &Board, key: u64) -> Score {
+    let entry = PAWN_TABLE.with(|table| {
+        let cached = table.borrow().probe(key);
+        return cached.unwrap_or_else(|| {
+            let entry = evaluate_pawns(board, key);
+            table.borrow_mut().store(entry);
+            entry
+        });
+    });
+    let occupied = board.combined().0;
+    let mut score = entry.score;
+    for color in ALL_COLORS {
+        let mut free = 0;
+        for sq in chess::BitBoard(entry.passed[color.to_index()]) {
+            if front_span(sq, color, get_file(sq.get_file()).0) & occupied == 0 {
+                free += PASSED_PAWN_FREE_PATH[relative_rank(sq, color)];
+            }
+        }
+        score += side_score(color, Score::new(0, free));
+    }
+    return score;
+}
+
+/// Evaluates doubled, isolated, backward and passed pawns from scratch.
+pub fn evaluate_pawns(board: &Board, key: u64) -> PawnEntry {
+    let pawns = *board.pieces(Piece::Pawn);
+    let mut score = Score::default();
+    let mut passed = [0; 2];
+    for color in ALL_COLORS {
+        let ours = (pawns & *board.color_combined(color)).0;
+        let theirs = (pawns & *board.color_combined(!color)).0;
+        let mut side = Score::default();
+        for sq in chess::BitBoard(ours) {
+            let file = get_file(sq.get_file()).0;
+            let adjacent = get_adjacent_files(sq.get_file()).0;
+
+            // another of our pawns in front of this one on the same file.
+            let is_doubled = front_span(sq, color, file) & ours != 0;
+            if is_doubled {
+                side += DOUBLED_PAWN;
+            }
+
+            if adjacent & ours == 0 {
+                side += ISOLATED_PAWN;
+            } else if is_backward(sq, color, ours, theirs, adjacent) {
+                side += BACKWARD_PAWN;
+            }
+
+            // only the front pawn of a doubled pair counts as passed.
+            if front_span(sq, color, file | adjacent) & theirs == 0 && !is_doubled {
+                side += PASSED_PAWN[relative_rank(sq, color)];
+                passed[color.to_index()] |= 1 << sq.to_index();
+            }
+        }
+        score += side_score(color, side);
+    }
+    return PawnEntry { key, score, passed };
+}
+
+// no pawn on an adjacent file can come to this pawn's support, and it can't safely advance
+// because its stop square is attacked by an enemy pawn.
+fn is_backward(sq: Square, color: Color, ours: u64, theirs: u64, adjacent: u64) -> bool {
+    let behind_or_level = !front_span(sq, color, !0);
+    if adjacent & behind_or_level & ours != 0 {
+        return false;
+    }
+    return match sq.forward(color) {
+        Some(stop) => chess::get_pawn_attacks(stop, color, chess::BitBoard(theirs)).0 != 0,
+        None => false,
+    };
+}
+
+// all squares in `files` strictly in front of `sq` from `color`'s point of view.
+fn front_span(sq: Square, color: Color, files: u64) -> u64 {
+    let rank = sq.get_rank().to_index();
+    let ranks = match color {
+        Color::White if rank == 7 => 0,
+        Color::White => !0u64 << (8 * (rank + 1)),
+        Color::Black => (1u64 << (8 * rank)) - 1,
+    };
+    return ranks & files;
+}
+
+fn relative_rank(sq: Square, color: Color) -> usize {
+    let rank = sq.get_rank().to_index();
+    return if color == Color::White {
+        rank
+    } else {
+        7 - rank
+    };
+}
+
+fn side_score(color: Color, score: Score) -> Score {
+    return if color == Color::White { score } else { -score };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    fn pawns_of(fen: &str) -> (Board, PawnEntry) {
+        let board = Board::from_str(fen).unwrap();
+        let entry = evaluate_pawns(&board, pawn_key(&board));
+        return (board, entry);
+    }
+
+    #[test]
+    fn symmetric_structure_is_even() {
+        let (board, entry) = pawns_of("4k3/pp3ppp/8/8/8/8/PP3PPP/4K3 w - - 0 1");
+        assert_eq!(entry.score, Score::default());
+        assert_eq!(pawn_structure(&board, entry.key), Score::default());
+    }
+
+    #[test]
+    fn doubled_and_isolated() {
+        // white has doubled, isolated pawns on the c file, black's pawns are healthy.
+        let (_, entry) = pawns_of("4k3/1pp5/8/8/8/2P5/2P5/4K3 w - - 0 1");
+        assert_eq!(entry.score, DOUBLED_PAWN + ISOLATED_PAWN + ISOLATED_PAWN);
+        assert_eq!(entry.passed, [0, 0]);
+    }
+
+    #[test]
+    fn passed_pawn_with_free_path() {
+        let (board, entry) = pawns_of("4k3/8/8/3P4/8/8/8/4K3 w - - 0 1");
+        assert_eq!(entry.passed[0], 1 << Square::D5.to_index());
+        let free = pawn_structure(&board, entry.key);
+        assert_eq!(
+            free,
+            ISOLATED_PAWN + PASSED_PAWN[4] + Score::new(0, PASSED_PAWN_FREE_PATH[4])
+        );
+
+        // a black king in front of the pawn takes the free path bonus away.
+        let blocked = Board::from_str("8/8/3k4/3P4/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            pawn_structure(&blocked, pawn_key(&blocked)),
+            ISOLATED_PAWN + PASSED_PAWN[4]
+        );
+    }
+
+    #[test]
+    fn backward_pawn() {
+        // d3 can't be supported by the e4 pawn and d4 is covered by the c5 pawn, e4 is passed
+        // and c5 is isolated.
+        let (_, entry) = pawns_of("4k3/8/8/2p5/4P3/3P4/8/4K3 w - - 0 1");
+        assert_eq!(entry.score, BACKWARD_PAWN + PASSED_PAWN[3] - ISOLATED_PAWN);
+        assert_eq!(entry.passed, [1 << Square::E4.to_index(), 0]);
+    }
+
+    #[test]
+    fn table_round_trip() {
+        let mut table = PawnHashTable::new(16);
+        let (_, entry) = pawns_of("4k3/pp6/8/8/8/8/5PPP/4K3 w - - 0 1");
+        assert_eq!(table.probe(entry.key), None);
+        table.store(entry);
+        assert_eq!(table.probe(entry.key), Some(entry));
+    }
+}
@@ -18,7 +18,7 @@ impl Tag {
 
 impl Display for Tag {
     fn fmt(&self, f: &mut Formatter) -> Result {
-        write!(f, "[{} \"{}\"]\n", self.name, self.value)
+        writeln!(f, "[{} \"{}\"]", self.name, self.value)
     }
 }
 
@@ -151,9 +151,11 @@ impl PgnMove {
     pub fn is_capture(m: ChessMove, b: &Board) -> bool {
         let color = b.color_on(m.get_source()).unwrap();
         if let Some(c) = b.color_on(m.get_dest()) {
-            c == color
+            c != color
         } else {
-            false
+            // a pawn moving diagonally onto an empty square takes en passant.
+            b.piece_on(m.get_source()) == Some(Piece::Pawn)
+                && m.get_source().get_file() != m.get_dest().get_file()
         }
     }
 
@@ -162,7 +164,7 @@ impl PgnMove {
         // then "&" it with the checking pieces on the board AFTER the move, the only way it could still be greater than 0 is if
         // this move was the one that caused the check.
         let dest_bitmask = get_rank(m.get_dest().get_rank()) & get_file(m.get_dest().get_file());
-        return (b.make_move_new(m).checkers().clone() & dest_bitmask).0 > 0;
+        return (*b.make_move_new(m).checkers() & dest_bitmask).0 > 0;
     }
 
     fn is_checkmate(m: ChessMove, b: &Board) -> bool {
@@ -187,19 +189,19 @@ impl Display for PgnMove {
     fn fmt(&self, f: &mut Formatter) -> Result {
         if self.is_castle() {
             if self.is_kingside_castle() {
-                write!(f, "{}", "O-O")?;
+                write!(f, "O-O")?;
             }
 
             if self.is_queenside_castle() {
-                write!(f, "{}", "O-O-O")?;
+                write!(f, "O-O-O")?;
             }
 
             if self.is_checkmate {
-                return write!(f, "{}", "#");
+                return write!(f, "#");
             }
 
             if self.is_check {
-                return write!(f, "{}", "+");
+                return write!(f, "+");
             }
 
             return Ok(());
@@ -207,6 +209,9 @@ impl Display for PgnMove {
 
         if let Some(pname) = self.to_piece_abbrev_str() {
             write!(f, "{}", pname)?;
+        } else if self.is_capture && !self.is_ambiguous {
+            // pawn captures are always written with the file they came from, e.g. "exd5".
+            write!(f, "{}", self.to_src_file_str())?;
         }
         // now handle if you need to write the name of the piece
         // should be "Ne6xf8" for example if its ambiguous capture.
@@ -216,7 +221,7 @@ impl Display for PgnMove {
 
         // now write the x if it was a capture
         if self.is_capture {
-            write!(f, "{}", "x")?;
+            write!(f, "x")?;
         }
 
         write!(f, "{}", self.to_dest_square_str())?;
@@ -226,11 +231,11 @@ impl Display for PgnMove {
         }
 
         if self.is_checkmate {
-            return write!(f, "{}", "#");
+            return write!(f, "#");
         }
 
         if self.is_check {
-            return write!(f, "{}", "+");
+            return write!(f, "+");
         }
 
         return Ok(());
@@ -296,7 +301,7 @@ impl PgnEncoder {
     }
 
     pub fn encode(&self) -> String {
-        let mut board: Board = self.initial_pos.clone();
+        let mut board: Board = self.initial_pos;
         let mut pgn = String::new();
         for tag in &self.tags {
             pgn.push_str(&tag.to_string());
@@ -331,6 +336,24 @@ mod test {
             Board::from_str("r4rk1/ppp2ppp/8/2b1Nbq1/2BnQ3/4B3/PPP2PPP/R4RK1 w - - 1 12").unwrap();
         let ambiguous_move = ChessMove::from_san(&board, "Rad1").unwrap();
         let pgn = PgnMove::from_board(ambiguous_move, &board);
-        assert_eq!(pgn.is_ambiguous, true);
+        assert!(pgn.is_ambiguous);
+    }
+
+    #[test]
+    fn test_captures() {
+        use super::PgnMove;
+        use chess::{Board, ChessMove};
+        use std::str::FromStr;
+        let board = Board::from_str("4k3/8/8/3p4/4P3/8/8/R3K3 w - - 0 1").unwrap();
+        let pawn_capture = ChessMove::from_str("e4d5").unwrap();
+        assert_eq!(
+            PgnMove::from_board(pawn_capture, &board).to_string(),
+            "exd5"
+        );
+        let quiet = ChessMove::from_str("a1a8").unwrap();
+        assert_eq!(PgnMove::from_board(quiet, &board).to_string(), "Ra8+");
+        let ep = Board::from_str("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        let ep_capture = ChessMove::from_str("e5d6").unwrap();
+        assert_eq!(PgnMove::from_board(ep_capture, &ep).to_string(), "exd6");
     }
 }
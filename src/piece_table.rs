@@ -1,4 +1,6 @@
 use chess::{Color, File, Piece, Rank, NUM_RANKS};
+use std::ops::{Add, AddAssign, Neg, Sub};
+
 // basic piece values.
 pub const PAWN: i32 = 100;
 pub const KNIGHT: i32 = 320;
@@ -7,21 +9,81 @@ pub const ROOK: i32 = 500;
 pub const QUEEN: i32 = 900;
 pub const KING: i32 = 20000;
 
+// game phase weights of the non-pawn pieces, a full board of them adds up to MAX_PHASE.
+pub const KNIGHT_PHASE: i32 = 1;
+pub const BISHOP_PHASE: i32 = 1;
+pub const ROOK_PHASE: i32 = 2;
+pub const QUEEN_PHASE: i32 = 4;
+pub const MAX_PHASE: i32 = 4 * KNIGHT_PHASE + 4 * BISHOP_PHASE + 4 * ROOK_PHASE + 2 * QUEEN_PHASE;
+
+/// A score that has a middlegame and an endgame component, blended by the game phase.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Score {
+    pub mg: i32,
+    pub eg: i32,
+}
+
+impl Score {
+    pub fn new(mg: i32, eg: i32) -> Self {
+        return Self { mg, eg };
+    }
+
+    /// Interpolates between the middlegame and endgame score, `phase` is MAX_PHASE with
+    /// all pieces on the board and 0 when only kings and pawns are left.
+    pub fn taper(&self, phase: i32) -> i32 {
+        let phase = phase.clamp(0, MAX_PHASE);
+        return (self.mg * phase + self.eg * (MAX_PHASE - phase)) / MAX_PHASE;
+    }
+}
+
+impl Add for Score {
+    type Output = Score;
+    fn add(self, other: Score) -> Score {
+        return Score::new(self.mg + other.mg, self.eg + other.eg);
+    }
+}
+
+impl Sub for Score {
+    type Output = Score;
+    fn sub(self, other: Score) -> Score {
+        return Score::new(self.mg - other.mg, self.eg - other.eg);
+    }
+}
+
+impl AddAssign for Score {
+    fn add_assign(&mut self, other: Score) {
+        self.mg += other.mg;
+        self.eg += other.eg;
+    }
+}
+
+impl Neg for Score {
+    type Output = Score;
+    fn neg(self) -> Score {
+        return Score::new(-self.mg, -self.eg);
+    }
+}
+
 pub struct PieceTable([i32; 64]);
 
 fn flip_rank(r: Rank) -> usize {
     return NUM_RANKS - r.to_index() - 1;
 }
 
+/// The index into a table for a piece of `color` on the square, tables are laid out a8 to h1
+/// from white's point of view and mirrored for black.
+pub fn table_index(color: Color, rank: Rank, file: File) -> usize {
+    let (r_idx, f_idx) = if color == Color::White {
+        (flip_rank(rank), file.to_index())
+    } else {
+        (rank.to_index(), file.to_index())
+    };
+    return (r_idx * NUM_RANKS) + f_idx;
+}
+
 impl PieceTable {
     pub fn eval_position(&self, color: Color, rank: Rank, file: File) -> i32 {
-        let (r_idx, f_idx) = if color == Color::White {
-            (flip_rank(rank), file.to_index())
-        } else {
-            (rank.to_index(), file.to_index())
-        };
-        let rc = (r_idx * NUM_RANKS) + f_idx;
-        return self.at_index(rc);
+        return self.at_index(table_index(color, rank, file));
     }
 
     pub fn eval_with_piece(&self, piece: Piece, color: Color, rank: Rank, file: File) -> i32 {
@@ -35,14 +97,30 @@ impl PieceTable {
     }
 }
 
-pub fn score_piece_position(piece: Piece, color: Color, rank: Rank, file: File) -> i32 {
+/// The value of a piece on a square in both the middlegame and the endgame.
+pub fn score_piece_position(piece: Piece, color: Color, rank: Rank, file: File) -> Score {
+    let (mid, end) = match piece {
+        Piece::Pawn => (&PAWN_TABLE, &PAWN_TABLE_END),
+        Piece::Knight => (&KNIGHT_TABLE, &KNIGHT_TABLE_END),
+        Piece::Bishop => (&BISHOP_TABLE, &BISHOP_TABLE_END),
+        Piece::Rook => (&ROOK_TABLE, &ROOK_TABLE_END),
+        Piece::Queen => (&QUEEN_TABLE, &QUEEN_TABLE_END),
+        Piece::King => (&KING_TABLE_MID, &KING_TABLE_END),
+    };
+    return Score::new(
+        mid.eval_with_piece(piece, color, rank, file),
+        end.eval_with_piece(piece, color, rank, file),
+    );
+}
+
+/// How much a piece contributes to the game phase, pawns and kings don't count.
+pub fn phase_weight(piece: Piece) -> i32 {
     match piece {
-        Piece::Pawn => PAWN_TABLE.eval_with_piece(piece, color, rank, file),
-        Piece::Knight => KNIGHT_TABLE.eval_with_piece(piece, color, rank, file),
-        Piece::Bishop => BISHOP_TABLE.eval_with_piece(piece, color, rank, file),
-        Piece::Rook => ROOK_TABLE.eval_with_piece(piece, color, rank, file),
-        Piece::Queen => QUEEN_TABLE.eval_with_piece(piece, color, rank, file),
-        Piece::King => KING_TABLE_MID.eval_with_piece(piece, color, rank, file),
+        Piece::Knight => KNIGHT_PHASE,
+        Piece::Bishop => BISHOP_PHASE,
+        Piece::Rook => ROOK_PHASE,
+        Piece::Queen => QUEEN_PHASE,
+        Piece::Pawn | Piece::King => 0,
     }
 }
 
@@ -57,6 +135,7 @@ pub fn piece_value(piece: Piece) -> i32 {
     }
 }
 
+// the tables without a suffix are used in the middlegame, the `_END` tables in the endgame.
 pub const PAWN_TABLE: PieceTable = PieceTable([
     0, 0, 0, 0, 0, 0, 0, 0, 50, 50, 50, 50, 50, 50, 50, 50, 10, 10, 20, 30, 30, 20, 10, 10, 5, 5,
     10, 25, 25, 10, 5, 5, 0, 0, 0, 20, 20, 0, 0, 0, 5, -5, -10, 0, 0, -10, -5, 5, 5, 10, 10, -20,
@@ -101,6 +180,37 @@ pub const KING_TABLE_END: PieceTable = PieceTable([
     -30, -50,
 ]);
 
+pub const PAWN_TABLE_END: PieceTable = PieceTable([
+    0, 0, 0, 0, 0, 0, 0, 0, 80, 80, 80, 80, 80, 80, 80, 80, 50, 50, 50, 50, 50, 50, 50, 50, 30, 30,
+    30, 30, 30, 30, 30, 30, 15, 15, 15, 15, 15, 15, 15, 15, 5, 5, 5, 5, 5, 5, 5, 5, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+]);
+
+pub const KNIGHT_TABLE_END: PieceTable = PieceTable([
+    -50, -40, -30, -30, -30, -30, -40, -50, -40, -20, -5, -5, -5, -5, -20, -40, -30, -5, 10, 15,
+    15, 10, -5, -30, -30, -5, 15, 20, 20, 15, -5, -30, -30, -5, 15, 20, 20, 15, -5, -30, -30, -5,
+    10, 15, 15, 10, -5, -30, -40, -20, -5, -5, -5, -5, -20, -40, -50, -40, -30, -30, -30, -30, -40,
+    -50,
+]);
+
+pub const BISHOP_TABLE_END: PieceTable = PieceTable([
+    -20, -10, -10, -10, -10, -10, -10, -20, -10, 0, 0, 0, 0, 0, 0, -10, -10, 0, 5, 5, 5, 5, 0, -10,
+    -10, 0, 5, 10, 10, 5, 0, -10, -10, 0, 5, 10, 10, 5, 0, -10, -10, 0, 5, 5, 5, 5, 0, -10, -10, 0,
+    0, 0, 0, 0, 0, -10, -20, -10, -10, -10, -10, -10, -10, -20,
+]);
+
+pub const ROOK_TABLE_END: PieceTable = PieceTable([
+    5, 5, 5, 5, 5, 5, 5, 5, 10, 10, 10, 10, 10, 10, 10, 10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 0,
+]);
+
+pub const QUEEN_TABLE_END: PieceTable = PieceTable([
+    -20, -10, -10, -5, -5, -10, -10, -20, -10, 0, 5, 5, 5, 5, 0, -10, -10, 5, 10, 10, 10, 10, 5,
+    -10, -5, 5, 10, 15, 15, 10, 5, -5, -5, 5, 10, 15, 15, 10, 5, -5, -10, 5, 10, 10, 10, 10, 5,
+    -10, -10, 0, 5, 5, 5, 5, 0, -10, -20, -10, -10, -5, -5, -10, -10, -20,
+]);
+
 mod test {
     #[test]
     fn test_piece_table() {
@@ -121,6 +231,25 @@ mod test {
         }
     }
 
+    #[test]
+    fn taper_blends_phases() {
+        use super::*;
+        let score = Score::new(100, -100);
+        assert_eq!(score.taper(MAX_PHASE), 100);
+        assert_eq!(score.taper(0), -100);
+        assert_eq!(score.taper(MAX_PHASE / 2), 0);
+        assert_eq!((-score).taper(MAX_PHASE / 3), -score.taper(MAX_PHASE / 3));
+    }
+
+    #[test]
+    fn king_prefers_center_in_endgame() {
+        use super::*;
+        let corner = score_piece_position(Piece::King, Color::White, Rank::First, File::G);
+        let center = score_piece_position(Piece::King, Color::White, Rank::Fourth, File::E);
+        assert!(corner.mg > center.mg);
+        assert!(corner.eg < center.eg);
+    }
+
     #[test]
     fn white_rook_seventh_rank() {
         use super::*;
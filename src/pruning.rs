@@ -0,0 +1,91 @@
+// defaults, every margin is in centipawns per ply of remaining depth.
+pub const DEFAULT_FUTILITY_MARGIN: i32 = 100;
+pub const DEFAULT_REVERSE_FUTILITY_MARGIN: i32 = 80;
+pub const DEFAULT_LMP_BASE: usize = 3;
+// the techniques only apply this close to the horizon, further away the static score says too
+// little about what the search will find.
+const FUTILITY_MAX_DEPTH: i8 = 3;
+const REVERSE_FUTILITY_MAX_DEPTH: i8 = 6;
+const LMP_MAX_DEPTH: i8 = 3;
+
+/// How aggressively quiet moves and whole nodes are pruned near the horizon. A value of 0
+/// turns the corresponding technique off.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PruningParams {
+    /// Quiet moves are skipped when the static score plus this much per ply can't reach alpha.
+    pub futility_margin: i32,
+    /// A node is cut off when the static score minus this much per ply still beats beta.
+    pub reverse_futility_margin: i32,
+    /// Quiet moves after the first `lmp_base + depth * depth` are skipped.
+    pub lmp_base: usize,
+}
+
+impl PruningParams {
+    pub fn new() -> Self {
+        return Self {
+            futility_margin: DEFAULT_FUTILITY_MARGIN,
+            reverse_futility_margin: DEFAULT_REVERSE_FUTILITY_MARGIN,
+            lmp_base: DEFAULT_LMP_BASE,
+        };
+    }
+
+    /// Searches every move of every node.
+    pub fn disabled() -> Self {
+        return Self {
+            futility_margin: 0,
+            reverse_futility_margin: 0,
+            lmp_base: 0,
+        };
+    }
+
+    /// The most a quiet move can be expected to gain at `depth`, None when futility pruning
+    /// doesn't apply.
+    pub fn futility_margin(&self, depth: i8) -> Option<i32> {
+        if self.futility_margin == 0 || depth > FUTILITY_MAX_DEPTH {
+            return None;
+        }
+        return Some(self.futility_margin * depth as i32);
+    }
+
+    /// How far above beta the static score has to be to cut off at `depth`, None when reverse
+    /// futility pruning doesn't apply.
+    pub fn reverse_futility_margin(&self, depth: i8) -> Option<i32> {
+        if self.reverse_futility_margin == 0 || depth > REVERSE_FUTILITY_MAX_DEPTH {
+            return None;
+        }
+        return Some(self.reverse_futility_margin * depth as i32);
+    }
+
+    /// How many quiet moves are searched at `depth` before the rest are skipped, None when
+    /// late move pruning doesn't apply.
+    pub fn late_move_count(&self, depth: i8) -> Option<usize> {
+        if self.lmp_base == 0 || depth > LMP_MAX_DEPTH {
+            return None;
+        }
+        let depth = depth.max(0) as usize;
+        return Some(self.lmp_base + depth * depth);
+    }
+}
+
+impl Default for PruningParams {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn margins_grow_with_depth_and_stop_far_from_the_horizon() {
+        let params = PruningParams::new();
+        assert!(params.futility_margin(2) > params.futility_margin(1));
+        assert_eq!(params.futility_margin(FUTILITY_MAX_DEPTH + 1), None);
+        assert_eq!(params.late_move_count(2), Some(DEFAULT_LMP_BASE + 4));
+        let off = PruningParams::disabled();
+        assert_eq!(off.futility_margin(1), None);
+        assert_eq!(off.reverse_futility_margin(1), None);
+        assert_eq!(off.late_move_count(1), None);
+    }
+}
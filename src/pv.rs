@@ -0,0 +1,68 @@
+use crate::move_order::MAX_PLY;
+use chess::ChessMove;
+
+/// Triangular principal variation table. The line stored at `ply` is the best move found at
+/// that ply followed by the line its child reported, so after the search the line at ply 0
+/// holds the variation the engine expects to be played.
+#[derive(Clone, Debug)]
+pub struct PvTable {
+    lines: Vec<Vec<ChessMove>>,
+}
+
+impl PvTable {
+    pub fn new() -> Self {
+        return Self {
+            lines: vec![Vec::new(); MAX_PLY + 1],
+        };
+    }
+
+    /// Forget the line at `ply`, called when a node is entered.
+    pub fn clear(&mut self, ply: usize) {
+        if let Some(line) = self.lines.get_mut(ply) {
+            line.clear();
+        }
+    }
+
+    /// `m` is the new best move at `ply`, so its line becomes `m` followed by the child's line.
+    pub fn update(&mut self, ply: usize, m: ChessMove) {
+        if ply >= MAX_PLY {
+            return;
+        }
+        let (head, tail) = self.lines.split_at_mut(ply + 1);
+        let line = &mut head[ply];
+        line.clear();
+        line.push(m);
+        line.extend_from_slice(&tail[0]);
+    }
+
+    pub fn line(&self, ply: usize) -> &[ChessMove] {
+        return self.lines.get(ply).map(|l| l.as_slice()).unwrap_or(&[]);
+    }
+}
+
+impl Default for PvTable {
+    fn default() -> Self {
+        return Self::new();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chess::Square;
+
+    #[test]
+    fn update_prepends_child_line() {
+        let a = ChessMove::new(Square::E2, Square::E4, None);
+        let b = ChessMove::new(Square::E7, Square::E5, None);
+        let c = ChessMove::new(Square::G1, Square::F3, None);
+        let mut pv = PvTable::new();
+        pv.update(2, c);
+        pv.update(1, b);
+        pv.update(0, a);
+        assert_eq!(pv.line(0), &[a, b, c]);
+        pv.clear(1);
+        pv.update(0, a);
+        assert_eq!(pv.line(0), &[a]);
+    }
+}
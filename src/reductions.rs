@@ -0,0 +1,71 @@
+// reductions grow with the log of both the depth and the move number, scaled by these two
+// (in hundredths, so they can be set as uci spin options).
+pub const DEFAULT_LMR_BASE: u32 = 75;
+pub const DEFAULT_LMR_DIVISOR: u32 = 225;
+// the first few moves of a node are searched at full depth no matter what.
+pub const LMR_MIN_MOVES: usize = 3;
+// below this depth there isn't enough left to reduce.
+pub const LMR_MIN_DEPTH: i8 = 3;
+
+const TABLE_SIZE: usize = 64;
+
+/// How many plies to take off late quiet moves, indexed by remaining depth and move number.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReductionTable {
+    table: Vec<[i8; TABLE_SIZE]>,
+}
+
+impl ReductionTable {
+    /// `base` and `divisor` are in hundredths: reduction = base + ln(depth) * ln(moves) / divisor.
+    pub fn new(base: u32, divisor: u32) -> Self {
+        let base = base as f64 / 100.0;
+        let divisor = divisor.max(1) as f64 / 100.0;
+        let table = (0..TABLE_SIZE)
+            .map(|depth| {
+                let mut row = [0; TABLE_SIZE];
+                for (moves, r) in row.iter_mut().enumerate().skip(1) {
+                    if depth > 0 {
+                        let reduction = base + (depth as f64).ln() * (moves as f64).ln() / divisor;
+                        *r = reduction.max(0.0) as i8;
+                    }
+                }
+                row
+            })
+            .collect();
+        return Self { table };
+    }
+
+    /// A table that never reduces anything.
+    pub fn disabled() -> Self {
+        return Self {
+            table: vec![[0; TABLE_SIZE]; TABLE_SIZE],
+        };
+    }
+
+    pub fn reduction(&self, depth: i8, move_number: usize) -> i8 {
+        let depth = (depth.max(0) as usize).min(TABLE_SIZE - 1);
+        return self.table[depth][move_number.min(TABLE_SIZE - 1)];
+    }
+}
+
+impl Default for ReductionTable {
+    fn default() -> Self {
+        return Self::new(DEFAULT_LMR_BASE, DEFAULT_LMR_DIVISOR);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reductions_grow_with_depth_and_move_number() {
+        let table = ReductionTable::default();
+        assert_eq!(table.reduction(1, 1), 0);
+        assert!(table.reduction(10, 30) > table.reduction(3, 4));
+        assert!(table.reduction(20, 40) >= table.reduction(20, 10));
+        // out of range lookups use the last entry.
+        assert_eq!(table.reduction(100, 100), table.reduction(63, 63));
+        assert_eq!(ReductionTable::disabled().reduction(20, 40), 0);
+    }
+}
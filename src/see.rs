@@ -0,0 +1,163 @@
+use crate::piece_table::{piece_value, PAWN};
+use chess::{
+    get_bishop_moves, get_king_moves, get_knight_moves, get_pawn_attacks, get_rook_moves, BitBoard,
+    Board, ChessMove, Color, Piece, Square, ALL_PIECES, EMPTY,
+};
+
+/// Static exchange evaluation: the material the side to move wins (or loses, if negative) by
+/// playing `m` when both sides then keep recapturing on the destination square with their
+/// least valuable piece for as long as it pays off. Sliders lined up behind other attackers
+/// (x-rays) join in as the pieces in front of them are traded off. Pins are ignored.
+///
+/// Works for quiet moves too, a quiet move to a square the opponent wins is negative.
+pub fn see(board: &Board, m: ChessMove) -> i32 {
+    let from = m.get_source();
+    let to = m.get_dest();
+    let Some(piece) = board.piece_on(from) else {
+        return 0;
+    };
+    let mut occupied = *board.combined() ^ BitBoard::from_square(from);
+
+    let mut captured = board.piece_on(to).map(piece_value).unwrap_or(0);
+    // en passant, the pawn we take is next to the square we land on.
+    if piece == Piece::Pawn && from.get_file() != to.get_file() && board.piece_on(to).is_none() {
+        captured = PAWN;
+        occupied ^= BitBoard::from_square(Square::make_square(from.get_rank(), to.get_file()));
+    }
+    let mut on_square = piece_value(piece);
+    if let Some(promotion) = m.get_promotion() {
+        captured += piece_value(promotion) - PAWN;
+        on_square = piece_value(promotion);
+    }
+
+    // gains[i] is what the side making the i-th capture is up if the exchange stops there.
+    let mut gains = vec![captured];
+    let mut side = !board.side_to_move();
+    loop {
+        let attackers = attackers_to(board, to, occupied) & board.color_combined(side);
+        let Some((sq, attacker)) = least_valuable(board, attackers) else {
+            break;
+        };
+        // the king can only take last, when nothing defends the square anymore.
+        let remaining = occupied ^ BitBoard::from_square(sq);
+        if attacker == Piece::King
+            && attackers_to(board, to, remaining) & board.color_combined(!side) != EMPTY
+        {
+            break;
+        }
+        gains.push(on_square - gains[gains.len() - 1]);
+        on_square = piece_value(attacker);
+        occupied = remaining;
+        side = !side;
+    }
+
+    // going backwards, each side either takes or stops, whichever is better for it.
+    while gains.len() > 1 {
+        let last = gains.pop().unwrap();
+        let previous = gains.last_mut().unwrap();
+        *previous = -(-*previous).max(last);
+    }
+    return gains[0];
+}
+
+/// Whether `m` gives away more material than it wins. Taking a piece worth at least as much as
+/// the one that moves can't lose anything, so the exchange is only worked out for the others.
+pub fn loses_material(board: &Board, m: ChessMove) -> bool {
+    let (Some(piece), Some(victim)) =
+        (board.piece_on(m.get_source()), board.piece_on(m.get_dest()))
+    else {
+        return see(board, m) < 0;
+    };
+    if piece_value(victim) >= piece_value(piece) {
+        return false;
+    }
+    return see(board, m) < 0;
+}
+
+/// Every piece of either color that attacks `sq`, considering only the pieces in `occupied`.
+pub fn attackers_to(board: &Board, sq: Square, occupied: BitBoard) -> BitBoard {
+    let pieces = |piece: Piece| *board.pieces(piece);
+    let diagonal = pieces(Piece::Bishop) | pieces(Piece::Queen);
+    let straight = pieces(Piece::Rook) | pieces(Piece::Queen);
+    let white_pawns = pieces(Piece::Pawn) & board.color_combined(Color::White);
+    let black_pawns = pieces(Piece::Pawn) & board.color_combined(Color::Black);
+    // a pawn attacks sq if a pawn of the other color on sq would attack it back.
+    let attackers = get_pawn_attacks(sq, Color::Black, white_pawns)
+        | get_pawn_attacks(sq, Color::White, black_pawns)
+        | (get_knight_moves(sq) & pieces(Piece::Knight))
+        | (get_king_moves(sq) & pieces(Piece::King))
+        | (get_bishop_moves(sq, occupied) & diagonal)
+        | (get_rook_moves(sq, occupied) & straight);
+    return attackers & occupied;
+}
+
+fn least_valuable(board: &Board, attackers: BitBoard) -> Option<(Square, Piece)> {
+    return ALL_PIECES.iter().find_map(|piece| {
+        let mut pieces = attackers & board.pieces(*piece);
+        return pieces.next().map(|sq| (sq, *piece));
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::piece_table::{BISHOP, KNIGHT, QUEEN, ROOK};
+    use std::str::FromStr;
+
+    fn see_uci(fen: &str, m: &str) -> i32 {
+        let board = Board::from_str(fen).unwrap();
+        return see(&board, ChessMove::from_str(m).unwrap());
+    }
+
+    #[test]
+    fn simple_exchanges() {
+        // an undefended pawn.
+        assert_eq!(see_uci("4k3/8/8/3p4/8/8/8/3RK3 w - - 0 1", "d1d5"), PAWN);
+        // a pawn defended by a pawn.
+        assert_eq!(
+            see_uci("4k3/8/4p3/3p4/8/8/8/3RK3 w - - 0 1", "d1d5"),
+            PAWN - ROOK
+        );
+        // the knight wins the bishop even though the pawn recaptures.
+        assert_eq!(
+            see_uci("4k3/8/4p3/3b4/8/4N3/8/4K3 w - - 0 1", "e3d5"),
+            BISHOP - KNIGHT
+        );
+        // the king can't take back, the rook behind still covers d5.
+        assert_eq!(see_uci("8/8/8/3r4/4k3/8/3R4/3RK3 w - - 0 1", "d2d5"), ROOK);
+    }
+
+    #[test]
+    fn x_rays() {
+        // the second rook behind the first one makes the exchange on d5 work.
+        let fen = "3r2k1/3r4/8/3p4/8/8/3R4/3RK3 w - - 0 1";
+        assert_eq!(see_uci(fen, "d2d5"), PAWN - ROOK);
+        let fen = "3r2k1/8/8/3p4/8/8/3R4/3QK3 w - - 0 1";
+        // rook takes, rook takes back, queen takes: a pawn up.
+        assert_eq!(see_uci(fen, "d2d5"), PAWN);
+        // a bishop behind a pawn.
+        let fen = "4k3/8/2q5/3r4/4P3/5B2/8/4K3 w - - 0 1";
+        assert_eq!(see_uci(fen, "e4d5"), ROOK);
+    }
+
+    #[test]
+    fn losing_captures() {
+        let board = Board::from_str("4k3/8/4p3/3p4/8/8/8/3QK2R w - - 0 1").unwrap();
+        assert!(loses_material(&board, ChessMove::from_str("d1d5").unwrap()));
+        assert!(!loses_material(
+            &board,
+            ChessMove::from_str("h1h8").unwrap()
+        ));
+    }
+
+    #[test]
+    fn quiet_moves_and_promotions() {
+        // walking the queen into a pawn.
+        assert_eq!(see_uci("4k3/8/4p3/8/8/8/8/3QK3 w - - 0 1", "d1d5"), -QUEEN);
+        assert_eq!(see_uci("4k3/8/8/8/8/8/8/3QK3 w - - 0 1", "d1d5"), 0);
+        // a promotion the rook takes back just loses the pawn.
+        assert_eq!(see_uci("r3k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7b8q"), -PAWN);
+        // en passant.
+        assert_eq!(see_uci("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"), PAWN);
+    }
+}
@@ -0,0 +1,190 @@
+use chess::Color;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_MOVE_OVERHEAD: u64 = 30;
+// when the gui doesn't tell us how many moves are left we plan for this many.
+const DEFAULT_MOVES_TO_GO: u64 = 30;
+// the hard limit may stretch the soft limit by this factor...
+const HARD_LIMIT_FACTOR: u64 = 4;
+// ...but never spends more than this share (in percent) of the clock on one move.
+const MAX_CLOCK_SHARE: u64 = 80;
+// a new iteration usually takes at least this many times longer than the last one.
+const ITERATION_GROWTH: u32 = 2;
+
+/// The clock information sent along with a `go` command, all values are in milliseconds.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TimeControl {
+    pub wtime: Option<u64>,
+    pub btime: Option<u64>,
+    pub winc: Option<u64>,
+    pub binc: Option<u64>,
+    pub movestogo: Option<u64>,
+    pub movetime: Option<u64>,
+}
+
+/// How long we want to think about a move. The soft limit is checked between iterative deepening
+/// iterations while the hard limit aborts the search wherever it is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeBudget {
+    pub soft: u64,
+    pub hard: u64,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TimeManager {
+    // time lost per move to communication with the gui, subtracted from everything we allocate.
+    move_overhead: u64,
+}
+
+impl TimeManager {
+    pub fn new(move_overhead: u64) -> Self {
+        return Self { move_overhead };
+    }
+
+    /// Turns the clock state into a budget for `side`. Returns None when the search has no time limit.
+    pub fn allocate(&self, control: &TimeControl, side: Color) -> Option<TimeBudget> {
+        if let Some(movetime) = control.movetime {
+            let limit = movetime.saturating_sub(self.move_overhead).max(1);
+            return Some(TimeBudget {
+                soft: limit,
+                hard: limit,
+            });
+        }
+
+        let (time, inc) = match side {
+            Color::White => (control.wtime, control.winc),
+            Color::Black => (control.btime, control.binc),
+        };
+        let time = time?;
+        let inc = inc.unwrap_or(0);
+        let moves_to_go = control.movestogo.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+
+        let available = time.saturating_sub(self.move_overhead).max(1);
+        let max_spend = (available * MAX_CLOCK_SHARE / 100).max(1);
+        let soft = (available / moves_to_go + inc * 3 / 4).clamp(1, max_spend);
+        let hard = (soft * HARD_LIMIT_FACTOR).min(max_spend);
+        return Some(TimeBudget { soft, hard });
+    }
+}
+
+impl Default for TimeManager {
+    fn default() -> Self {
+        return Self::new(DEFAULT_MOVE_OVERHEAD);
+    }
+}
+
+/// Decides whether iterative deepening should start another iteration. We stop once the soft
+/// limit has passed, or when the next iteration would most likely be cut off by the hard limit
+/// anyway (the time spent on it would be wasted).
+pub fn can_start_iteration(
+    now: Instant,
+    last_iteration: Duration,
+    soft: Option<Instant>,
+    hard: Option<Instant>,
+) -> bool {
+    if let Some(soft) = soft {
+        if now >= soft {
+            return false;
+        }
+    }
+    if let Some(hard) = hard {
+        if now + last_iteration * ITERATION_GROWTH >= hard {
+            return false;
+        }
+    }
+    return true;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn movetime_is_used_as_is() {
+        let control = TimeControl {
+            movetime: Some(1000),
+            ..Default::default()
+        };
+        let budget = TimeManager::new(50).allocate(&control, Color::White);
+        assert_eq!(
+            budget,
+            Some(TimeBudget {
+                soft: 950,
+                hard: 950
+            })
+        );
+    }
+
+    #[test]
+    fn no_clock_means_no_limit() {
+        let control = TimeControl::default();
+        assert_eq!(
+            TimeManager::default().allocate(&control, Color::Black),
+            None
+        );
+    }
+
+    #[test]
+    fn uses_own_clock_and_increment() {
+        let control = TimeControl {
+            wtime: Some(60_000),
+            btime: Some(1_000),
+            winc: Some(1_000),
+            binc: Some(0),
+            movestogo: Some(20),
+            movetime: None,
+        };
+        let tm = TimeManager::new(0);
+        let white = tm.allocate(&control, Color::White).unwrap();
+        assert_eq!(white.soft, 60_000 / 20 + 750);
+        assert_eq!(white.hard, white.soft * HARD_LIMIT_FACTOR);
+        let black = tm.allocate(&control, Color::Black).unwrap();
+        assert_eq!(black.soft, 50);
+        assert!(black.hard <= 800);
+    }
+
+    #[test]
+    fn never_flags_on_last_move() {
+        let control = TimeControl {
+            wtime: Some(500),
+            movestogo: Some(1),
+            ..Default::default()
+        };
+        let budget = TimeManager::new(100)
+            .allocate(&control, Color::White)
+            .unwrap();
+        assert!(budget.hard < 400);
+        assert!(budget.soft <= budget.hard);
+    }
+
+    #[test]
+    fn iteration_prediction() {
+        let now = Instant::now();
+        let soft = Some(now + Duration::from_millis(500));
+        let hard = Some(now + Duration::from_millis(1000));
+        assert!(can_start_iteration(
+            now,
+            Duration::from_millis(100),
+            soft,
+            hard
+        ));
+        assert!(!can_start_iteration(
+            now,
+            Duration::from_millis(600),
+            soft,
+            hard
+        ));
+        assert!(!can_start_iteration(
+            now + Duration::from_millis(500),
+            Duration::ZERO,
+            soft,
+            hard
+        ));
+        assert!(can_start_iteration(
+            now,
+            Duration::from_secs(60),
+            None,
+            None
+        ));
+    }
+}
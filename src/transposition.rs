@@ -0,0 +1,247 @@
+use chess::{ChessMove, Piece, ALL_SQUARES};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+pub const DEFAULT_HASH_MB: usize = 16;
+pub const MIN_HASH_MB: usize = 1;
+pub const MAX_HASH_MB: usize = 128;
+
+// each slot holds two u64 words (key and data).
+const SLOT_BYTES: usize = 16;
+
+/// Describes how the stored score relates to the true score of the position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bound {
+    // the score is the true minimax value of the position.
+    Exact,
+    // the search failed high, the true score is at least this large.
+    Lower,
+    // the search failed low, the true score is at most this large.
+    Upper,
+}
+
+impl Bound {
+    // never 0, so that a packed entry is never 0 either, which marks an empty slot.
+    fn to_bits(self) -> u64 {
+        match self {
+            Bound::Exact => 1,
+            Bound::Lower => 2,
+            Bound::Upper => 3,
+        }
+    }
+
+    fn from_bits(bits: u64) -> Self {
+        match bits {
+            2 => Bound::Lower,
+            3 => Bound::Upper,
+            _ => Bound::Exact,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TTEntry {
+    pub best_move: Option<ChessMove>,
+    pub depth: i8,
+    pub bound: Bound,
+    pub score: i32,
+}
+
+impl TTEntry {
+    pub fn new(best_move: Option<ChessMove>, depth: i8, bound: Bound, score: i32) -> Self {
+        return Self {
+            best_move,
+            depth,
+            bound,
+            score,
+        };
+    }
+
+    // layout: score (32 bits) | depth (8 bits) | bound (2 bits) | move (16 bits)
+    fn pack(&self) -> u64 {
+        let score = (self.score as u32) as u64;
+        let depth = (self.depth as u8) as u64;
+        let bound = self.bound.to_bits();
+        let mv = pack_move(self.best_move);
+        return (score << 32) | (depth << 24) | (bound << 16) | mv;
+    }
+
+    fn unpack(data: u64) -> Self {
+        let score = (data >> 32) as u32 as i32;
+        let depth = ((data >> 24) & 0xff) as u8 as i8;
+        let bound = Bound::from_bits((data >> 16) & 0x3);
+        let best_move = unpack_move(data & 0xffff);
+        return Self::new(best_move, depth, bound, score);
+    }
+}
+
+// a move is encoded as: promotion (3 bits) | from (6 bits) | to (6 bits), with the
+// top bit set so that "no move" (0) can be distinguished from a1a1.
+fn pack_move(m: Option<ChessMove>) -> u64 {
+    match m {
+        None => 0,
+        Some(m) => {
+            let promo = match m.get_promotion() {
+                None => 0,
+                Some(Piece::Knight) => 1,
+                Some(Piece::Bishop) => 2,
+                Some(Piece::Rook) => 3,
+                Some(_) => 4,
+            };
+            let from = m.get_source().to_index() as u64;
+            let to = m.get_dest().to_index() as u64;
+            (1 << 15) | (promo << 12) | (from << 6) | to
+        }
+    }
+}
+
+fn unpack_move(bits: u64) -> Option<ChessMove> {
+    if bits & (1 << 15) == 0 {
+        return None;
+    }
+    let promo = match (bits >> 12) & 0x7 {
+        0 => None,
+        1 => Some(Piece::Knight),
+        2 => Some(Piece::Bishop),
+        3 => Some(Piece::Rook),
+        _ => Some(Piece::Queen),
+    };
+    let from = ALL_SQUARES[((bits >> 6) & 0x3f) as usize];
+    let to = ALL_SQUARES[(bits & 0x3f) as usize];
+    return Some(ChessMove::new(from, to, promo));
+}
+
+struct TTSlot {
+    // the position hash xor'd with the data, so a torn write from another thread
+    // simply fails to verify on probe instead of returning corrupt data.
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+impl TTSlot {
+    fn empty() -> Self {
+        return Self {
+            key: AtomicU64::new(0),
+            data: AtomicU64::new(0),
+        };
+    }
+}
+
+/// A fixed size, lock-free transposition table that can be shared between search threads.
+pub struct TranspositionTable {
+    slots: Vec<TTSlot>,
+    mask: usize,
+}
+
+impl TranspositionTable {
+    /// Create a table using roughly `mb` megabytes of memory (rounded down to a power of two number of slots).
+    pub fn new(mb: usize) -> Self {
+        let mb = mb.clamp(MIN_HASH_MB, MAX_HASH_MB);
+        let target = (mb * 1024 * 1024) / SLOT_BYTES;
+        // round down to a power of two so we can mask instead of mod.
+        let len = 1 << (usize::BITS - 1 - target.leading_zeros());
+        let slots = (0..len).map(|_| TTSlot::empty()).collect();
+        return Self {
+            slots,
+            mask: len - 1,
+        };
+    }
+
+    pub fn len(&self) -> usize {
+        return self.slots.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.slots.is_empty();
+    }
+
+    pub fn probe(&self, hash: u64) -> Option<TTEntry> {
+        let slot = &self.slots[hash as usize & self.mask];
+        let data = slot.data.load(Ordering::Relaxed);
+        let key = slot.key.load(Ordering::Relaxed);
+        if data == 0 || key ^ data != hash {
+            return None;
+        }
+        return Some(TTEntry::unpack(data));
+    }
+
+    pub fn store(&self, hash: u64, entry: TTEntry) {
+        let slot = &self.slots[hash as usize & self.mask];
+        let old_data = slot.data.load(Ordering::Relaxed);
+        let old_key = slot.key.load(Ordering::Relaxed);
+        // keep deeper results for the same position unless the new one is exact.
+        if old_key ^ old_data == hash && old_data != 0 {
+            let old = TTEntry::unpack(old_data);
+            if old.depth > entry.depth && entry.bound != Bound::Exact {
+                return;
+            }
+        }
+        let data = entry.pack();
+        slot.data.store(data, Ordering::Relaxed);
+        slot.key.store(hash ^ data, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        for slot in self.slots.iter() {
+            slot.data.store(0, Ordering::Relaxed);
+            slot.key.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        return Self::new(DEFAULT_HASH_MB);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chess::{Board, MoveGen, Square};
+
+    #[test]
+    fn entry_round_trip() {
+        let m = ChessMove::new(Square::A7, Square::B8, Some(Piece::Knight));
+        let entry = TTEntry::new(Some(m), 7, Bound::Lower, -31000);
+        assert_eq!(TTEntry::unpack(entry.pack()), entry);
+
+        let no_move = TTEntry::new(None, -1, Bound::Upper, 42);
+        assert_eq!(TTEntry::unpack(no_move.pack()), no_move);
+    }
+
+    #[test]
+    fn store_and_probe() {
+        let tt = TranspositionTable::new(1);
+        assert!(tt.len().is_power_of_two());
+        let board = Board::default();
+        let m = MoveGen::new_legal(&board).next().unwrap();
+        let entry = TTEntry::new(Some(m), 3, Bound::Exact, 25);
+        tt.store(board.get_hash(), entry);
+        assert_eq!(tt.probe(board.get_hash()), Some(entry));
+        // a different position mapping to another slot (or failing the key check) misses.
+        let other = board.make_move_new(m);
+        assert_eq!(tt.probe(other.get_hash()), None);
+        tt.clear();
+        assert_eq!(tt.probe(board.get_hash()), None);
+    }
+
+    #[test]
+    fn keeps_deeper_entry() {
+        let tt = TranspositionTable::new(1);
+        let hash = Board::default().get_hash();
+        tt.store(hash, TTEntry::new(None, 6, Bound::Lower, 10));
+        tt.store(hash, TTEntry::new(None, 2, Bound::Upper, -10));
+        assert_eq!(tt.probe(hash).unwrap().depth, 6);
+        tt.store(hash, TTEntry::new(None, 2, Bound::Exact, 5));
+        assert_eq!(tt.probe(hash).unwrap().depth, 2);
+    }
+
+    #[test]
+    fn stores_a_draw_at_depth_zero() {
+        let tt = TranspositionTable::new(1);
+        let hash = Board::default().get_hash();
+        let draw = TTEntry::new(None, 0, Bound::Exact, 0);
+        tt.store(hash, draw);
+        assert_eq!(tt.probe(hash), Some(draw));
+    }
+}
@@ -0,0 +1,328 @@
+use crate::eval_params::EvalParams;
+use crate::evaluation::{quiescence, BoardState, NegaMaxOptions, SearchContext, MIN_SCORE};
+use crate::evaluator::PstEvaluator;
+use crate::pgn::PgnMove;
+use chess::{Board, ChessMove, Color, MoveGen, EMPTY};
+use rayon::prelude::*;
+use std::str::FromStr;
+use std::sync::Arc;
+
+// the first few moves of a game say more about the opening book than about the evaluation.
+const SKIPPED_OPENING_PLIES: usize = 8;
+
+/// A position and the result of the game it was taken from, from white's point of view
+/// (1 for a win, 0.5 for a draw and 0 for a loss).
+#[derive(Clone, Debug)]
+pub struct TuningPosition {
+    pub board: Board,
+    pub result: f64,
+}
+
+/// Turns a game result into a score for white, accepts both "1-0" and "1.0" styles.
+pub fn parse_result(token: &str) -> Option<f64> {
+    let token = token.trim_matches(|c| c == '"' || c == ';' || c == '[' || c == ']');
+    match token {
+        "1-0" | "1.0" | "1" => Some(1.0),
+        "0-1" | "0.0" | "0" => Some(0.0),
+        "1/2-1/2" | "0.5" => Some(0.5),
+        _ => None,
+    }
+}
+
+/// Reads EPD lines with the result either as a `c9 "1-0";` opcode or in brackets (`[0.5]`).
+/// Lines that can't be read are skipped.
+pub fn load_epd(text: &str) -> Vec<TuningPosition> {
+    let mut positions = Vec::new();
+    for line in text.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 5 {
+            continue;
+        }
+        let board = match Board::from_str(&fields[..4].join(" ")) {
+            Ok(board) => board,
+            Err(_) => continue,
+        };
+        if let Some(result) = fields[4..].iter().find_map(|f| parse_result(f)) {
+            positions.push(TuningPosition { board, result });
+        }
+    }
+    return positions;
+}
+
+/// Reads every position of every finished game in a PGN file, such as the ones `UCITestEngine`
+/// writes. Games without a result are skipped, as are the opening moves and positions where
+/// the side to move is in check.
+pub fn load_pgn(text: &str) -> Vec<TuningPosition> {
+    let mut positions = Vec::new();
+    let mut game: Vec<Board> = Vec::new();
+    let mut board = Board::default();
+    let mut is_broken = false;
+    for token in pgn_tokens(text) {
+        if token == "*" || parse_result(&token).is_some() {
+            if let (false, Some(result)) = (is_broken, parse_result(&token)) {
+                positions.extend(
+                    game.drain(..)
+                        .skip(SKIPPED_OPENING_PLIES)
+                        .filter(|b| *b.checkers() == EMPTY)
+                        .map(|board| TuningPosition { board, result }),
+                );
+            }
+            game.clear();
+            board = Board::default();
+            is_broken = false;
+            continue;
+        }
+        if is_broken {
+            continue;
+        }
+        match parse_san(&board, &token) {
+            Some(m) => {
+                board = board.make_move_new(m);
+                game.push(board);
+            }
+            // we lost track of the game, drop it.
+            None => is_broken = true,
+        }
+    }
+    return positions;
+}
+
+// the moves and results of a pgn file, without tags, comments and move numbers.
+fn pgn_tokens(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut in_comment = false;
+    for line in text.lines() {
+        if line.trim_start().starts_with('[') {
+            continue;
+        }
+        for word in line.split_whitespace() {
+            if in_comment {
+                in_comment = !word.ends_with('}');
+                continue;
+            }
+            if word.starts_with('{') {
+                in_comment = !word.ends_with('}');
+                continue;
+            }
+            // our own encoder glues the move number to the move ("1.e4").
+            let word = if parse_result(word).is_some() {
+                word
+            } else {
+                word.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.')
+            };
+            if !word.is_empty() {
+                tokens.push(word.to_string());
+            }
+        }
+    }
+    return tokens;
+}
+
+fn parse_san(board: &Board, san: &str) -> Option<ChessMove> {
+    let san = san.trim_end_matches(['+', '#', '!', '?']);
+    let ours = MoveGen::new_legal(board).find(|m| {
+        PgnMove::from_board(*m, board)
+            .to_string()
+            .trim_end_matches(['+', '#'])
+            == san
+    });
+    return ours.or_else(|| ChessMove::from_san(board, san).ok());
+}
+
+/// Maps a centipawn score to an expected result, `k` scales how quickly an advantage turns
+/// into a won game.
+pub fn sigmoid(score: f64, k: f64) -> f64 {
+    return 1.0 / (1.0 + 10f64.powf(-k * score / 400.0));
+}
+
+/// The quiescence score of a position for white.
+pub fn quiet_score(
+    board: &Board,
+    eval: &Arc<PstEvaluator>,
+    opts: &NegaMaxOptions,
+    ctx: &mut SearchContext,
+) -> i32 {
+    let state = BoardState::with_evaluator(*board, eval.clone());
+    let score = quiescence(&state, 0, MIN_SCORE, -MIN_SCORE, opts, ctx).score;
+    if board.side_to_move() == Color::White {
+        return score;
+    }
+    return -score;
+}
+
+/// How far the evaluation with `params` is from the actual results.
+pub fn mean_squared_error(positions: &[TuningPosition], params: &EvalParams, k: f64) -> f64 {
+    if positions.is_empty() {
+        return 0.0;
+    }
+    let eval = Arc::new(PstEvaluator::new().params(params.clone()));
+    // no limits, every position is scored in full.
+    let opts = NegaMaxOptions::new();
+    let total: f64 = positions
+        .par_iter()
+        .map_init(SearchContext::new, |ctx, p| {
+            let predicted = sigmoid(quiet_score(&p.board, &eval, &opts, ctx) as f64, k);
+            (p.result - predicted).powi(2)
+        })
+        .sum();
+    return total / positions.len() as f64;
+}
+
+/// Texel tuning: nudges one parameter at a time by `step` and keeps every change that lowers
+/// the error, until a full pass over the parameters doesn't improve anything.
+pub struct Tuner {
+    positions: Vec<TuningPosition>,
+    k: Option<f64>,
+    step: i32,
+    max_iterations: usize,
+}
+
+impl Tuner {
+    pub fn new(positions: Vec<TuningPosition>) -> Self {
+        return Self {
+            positions,
+            k: None,
+            step: 1,
+            max_iterations: 100,
+        };
+    }
+
+    /// Use a fixed scaling constant instead of fitting one to the starting parameters.
+    pub fn k(mut self, k: f64) -> Self {
+        self.k = Some(k);
+        return self;
+    }
+
+    pub fn step(mut self, step: i32) -> Self {
+        self.step = step.max(1);
+        return self;
+    }
+
+    pub fn max_iterations(mut self, n: usize) -> Self {
+        self.max_iterations = n;
+        return self;
+    }
+
+    pub fn positions(&self) -> &[TuningPosition] {
+        return &self.positions;
+    }
+
+    /// Finds the scaling constant that fits the results best with `params`.
+    pub fn fit_k(&self, params: &EvalParams) -> f64 {
+        let mut best = (f64::MAX, 1.0);
+        for i in 1..=30 {
+            let k = i as f64 * 0.1;
+            let error = mean_squared_error(&self.positions, params, k);
+            if error < best.0 {
+                best = (error, k);
+            }
+        }
+        return best.1;
+    }
+
+    /// Tunes `params`, `on_iteration` is called after every pass with the pass number, the
+    /// error and the parameters so far.
+    pub fn run(
+        &self,
+        params: &EvalParams,
+        mut on_iteration: impl FnMut(usize, f64, &EvalParams),
+    ) -> EvalParams {
+        let k = self.k.unwrap_or_else(|| self.fit_k(params));
+        let mut best = params.clone();
+        let mut values = best.tunable();
+        let mut best_error = mean_squared_error(&self.positions, &best, k);
+        let mut candidate = best.clone();
+
+        for iteration in 1..=self.max_iterations {
+            let mut improved = false;
+            for i in 0..values.len() {
+                for delta in [self.step, -self.step] {
+                    values[i] += delta;
+                    candidate.set_tunable(&values);
+                    let error = mean_squared_error(&self.positions, &candidate, k);
+                    if error < best_error {
+                        best_error = error;
+                        best = candidate.clone();
+                        improved = true;
+                        break;
+                    }
+                    values[i] -= delta;
+                }
+            }
+            on_iteration(iteration, best_error, &best);
+            if !improved {
+                break;
+            }
+        }
+        return best;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pgn::{PgnEncoder, PgnOutcome};
+    use chess::GameResult;
+
+    #[test]
+    fn epd_results() {
+        let epd = "4k3/8/8/8/8/8/4P3/4K3 w - - c9 \"1-0\";\n\
+                   4k3/8/8/8/8/8/8/4K3 b - - [0.5]\n\
+                   not a position\n\
+                   4k3/8/8/8/8/8/8/4K3 w - - no result\n";
+        let positions = load_epd(epd);
+        assert_eq!(positions.len(), 2);
+        assert_eq!(positions[0].result, 1.0);
+        assert_eq!(positions[1].result, 0.5);
+        assert_eq!(positions[1].board.side_to_move(), Color::Black);
+    }
+
+    #[test]
+    fn reads_our_own_pgn() {
+        // captures and castling, ending in a black win.
+        let moves = [
+            "e2e4", "d7d5", "e4d5", "g8f6", "g1f3", "f6d5", "f1c4", "e7e6", "e1g1", "f8c5", "c4d5",
+            "e6d5", "d2d4", "c5d4", "f3d4", "d8h4", "b1c3",
+        ];
+        let mut board = Board::default();
+        let mut encoder = PgnEncoder::new(board, None);
+        for m in moves {
+            let m = ChessMove::from_str(m).unwrap();
+            assert!(board.legal(m));
+            board = board.make_move_new(m);
+            encoder.add_move(m);
+        }
+        encoder.set_outcome(PgnOutcome::from(GameResult::WhiteResigns));
+        let pgn = encoder.encode();
+
+        let positions = load_pgn(&format!("[Event \"test\"]\n{}\n\n{}", pgn, pgn));
+        assert_eq!(positions.len(), 2 * (moves.len() - SKIPPED_OPENING_PLIES));
+        assert!(positions.iter().all(|p| p.result == 0.0));
+        assert_eq!(positions.last().unwrap().board, board);
+
+        // an unfinished game teaches us nothing.
+        assert!(load_pgn("1.e4 e5 2.Nf3 Nc6 3.Bb5 a6 4.Ba4 Nf6 5.O-O *").is_empty());
+    }
+
+    #[test]
+    fn sigmoid_is_centered() {
+        assert_eq!(sigmoid(0.0, 1.0), 0.5);
+        assert!(sigmoid(400.0, 1.0) > 0.9);
+        assert!(sigmoid(-400.0, 1.0) < 0.1);
+    }
+
+    #[test]
+    fn tuning_lowers_the_error() {
+        // white keeps winning with an extra pawn, so pawns should be worth more.
+        let epd = "4k3/8/8/8/8/8/3PP3/4K3 w - - [1.0]\n\
+                   4k3/3p4/8/8/8/8/3PP3/4K3 b - - [1.0]\n\
+                   4k3/3pp3/8/8/8/8/4P3/4K3 w - - [0.0]\n";
+        let tuner = Tuner::new(load_epd(epd)).k(1.0).step(20).max_iterations(1);
+        let params = EvalParams::new();
+        let before = mean_squared_error(tuner.positions(), &params, 1.0);
+        let mut passes = 0;
+        let tuned = tuner.run(&params, |_, _, _| passes += 1);
+        assert_eq!(passes, 1);
+        assert!(mean_squared_error(tuner.positions(), &tuned, 1.0) < before);
+    }
+}
@@ -1,21 +1,36 @@
-use crate::engine::Engine;
+use crate::engine::{
+    Engine, DEFAULT_MULTI_PV, DEFAULT_THREADS, EVALUATORS, MAX_MULTI_PV, MAX_THREADS,
+};
 use crate::evaluation::NegaMaxOptions;
+use crate::game_history::GameHistory;
 use crate::pgn::PgnEncoder;
+use crate::pruning::{DEFAULT_FUTILITY_MARGIN, DEFAULT_LMP_BASE, DEFAULT_REVERSE_FUTILITY_MARGIN};
+use crate::reductions::{DEFAULT_LMR_BASE, DEFAULT_LMR_DIVISOR};
+use crate::time_manager::{TimeControl, TimeManager, DEFAULT_MOVE_OVERHEAD};
+use crate::worker::EngineWorker;
 use chess::{Board, ChessMove, Game};
 use std::collections::HashMap;
 use std::fs::create_dir_all;
 use std::io::{stdin, stdout, BufRead, BufReader, Write};
+use std::marker::PhantomData;
 use std::process::{Child, Command, Stdio};
 use std::str::FromStr;
-use std::thread::spawn;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 pub type UCIEngineOptions = HashMap<String, String>;
 
 pub struct UCIEngine<T: Engine + Send + 'static> {
     stdin: BufReader<std::io::Stdin>,
     stdout: std::io::Stdout,
-    engine: Option<T>,
+    // the engine lives on this worker thread from the first search until "quit".
+    worker: Option<EngineWorker>,
+    // set when an option changed after the worker built its engine, the next search rebuilds it.
+    options_changed: bool,
+    // shared with the running search, setting it makes the search return as soon as possible.
+    signal: Arc<AtomicBool>,
     initializer: fn(opts: UCIEngineOptions) -> T,
+    engine: PhantomData<T>,
     opts: UCIEngineOptions,
     name: String,
     author: String,
@@ -23,6 +38,8 @@ pub struct UCIEngine<T: Engine + Send + 'static> {
     pub reg_later: bool,
     pub debug: bool,
     board: Board,
+    // the positions leading up to `board`, for repetitions.
+    history: GameHistory,
 }
 
 impl<T: Engine + Send + 'static> UCIEngine<T> {
@@ -31,8 +48,11 @@ impl<T: Engine + Send + 'static> UCIEngine<T> {
         Self {
             stdin: BufReader::new(stdin()),
             stdout: stdout(),
-            engine: None,
+            worker: None,
+            options_changed: false,
+            signal: Arc::new(AtomicBool::new(false)),
             initializer,
+            engine: PhantomData,
             opts: HashMap::new(),
             name: "Rust Engine".to_string(),
             author: "Jacob Policano".to_string(),
@@ -40,6 +60,7 @@ impl<T: Engine + Send + 'static> UCIEngine<T> {
             reg_later: false,
             debug: false,
             board: Board::default(),
+            history: GameHistory::new(),
         }
     }
 
@@ -49,14 +70,16 @@ impl<T: Engine + Send + 'static> UCIEngine<T> {
             let mut line = String::new();
 
             if self.stdin.read_line(&mut line)? == 0 {
-                break; // EOF reached
+                // EOF reached, stop a running search the same way quit does.
+                self.handle_quit()?;
+                break;
             }
 
             if line.is_empty() {
                 continue;
             }
 
-            let parts: Vec<&str> = line.trim().split_whitespace().collect();
+            let parts: Vec<&str> = line.split_whitespace().collect();
             match parts[0] {
                 "uci" => self.handle_uci()?,
                 "isready" => self.handle_isready()?,
@@ -68,7 +91,10 @@ impl<T: Engine + Send + 'static> UCIEngine<T> {
                 "stop" => self.handle_stop()?,
                 "ponderhit" => self.handle_ponderhit()?,
                 "debug" => self.handle_debug(&parts[1..])?,
-                "quit" => break,
+                "quit" => {
+                    self.handle_quit()?;
+                    break;
+                }
                 _ => (),
             }
         }
@@ -85,6 +111,62 @@ impl<T: Engine + Send + 'static> UCIEngine<T> {
             self.stdout,
             "option name Hash type spin default 16 min 1 max 128"
         )?;
+        writeln!(
+            self.stdout,
+            "option name Threads type spin default {} min 1 max {}",
+            DEFAULT_THREADS, MAX_THREADS
+        )?;
+        writeln!(
+            self.stdout,
+            "option name MultiPV type spin default {} min 1 max {}",
+            DEFAULT_MULTI_PV, MAX_MULTI_PV
+        )?;
+        writeln!(
+            self.stdout,
+            "option name Move Overhead type spin default {} min 0 max 5000",
+            DEFAULT_MOVE_OVERHEAD
+        )?;
+        for term in ["Mobility", "Bishop Pair", "Rook Open File", "Rook Seventh"] {
+            writeln!(self.stdout, "option name {} type check default true", term)?;
+        }
+        let vars: Vec<String> = EVALUATORS.iter().map(|e| format!("var {}", e)).collect();
+        writeln!(
+            self.stdout,
+            "option name Evaluator type combo default {} {}",
+            EVALUATORS[0],
+            vars.join(" ")
+        )?;
+        writeln!(
+            self.stdout,
+            "option name EvalFile type string default <empty>"
+        )?;
+        // late move reductions are base + ln(depth) * ln(move number) / divisor, in hundredths.
+        writeln!(
+            self.stdout,
+            "option name LMR Base type spin default {} min 0 max 500",
+            DEFAULT_LMR_BASE
+        )?;
+        writeln!(
+            self.stdout,
+            "option name LMR Divisor type spin default {} min 50 max 1000",
+            DEFAULT_LMR_DIVISOR
+        )?;
+        // pruning margins are in centipawns per ply of depth, 0 turns the technique off.
+        writeln!(
+            self.stdout,
+            "option name Futility Margin type spin default {} min 0 max 1000",
+            DEFAULT_FUTILITY_MARGIN
+        )?;
+        writeln!(
+            self.stdout,
+            "option name Reverse Futility Margin type spin default {} min 0 max 1000",
+            DEFAULT_REVERSE_FUTILITY_MARGIN
+        )?;
+        writeln!(
+            self.stdout,
+            "option name LMP Base type spin default {} min 0 max 64",
+            DEFAULT_LMP_BASE
+        )?;
         // (Add additional options here as desired.)
         writeln!(self.stdout, "uciok")?;
         self.stdout.flush()?;
@@ -100,21 +182,24 @@ impl<T: Engine + Send + 'static> UCIEngine<T> {
 
     /// Parses the "setoption" command of the form:
     ///     setoption name <id> [value <x>]
-    /// and stores the option in the opts map.
+    /// and stores the option in the opts map. An engine that is already running picks the new
+    /// value up before its next search.
     fn handle_setoption(&mut self, line: &[&str]) -> Result<(), std::io::Error> {
         let mut toke_iter = line.iter();
         let mut name_pieces = Vec::new();
         let mut value_pieces = Vec::new();
         while let Some(toke) = toke_iter.next() {
             if *toke == "name" {
-                while let Some(toke) = toke_iter.next() {
+                for toke in toke_iter.by_ref() {
                     if *toke == "value" {
+                        // the rest of the line is the value.
+                        value_pieces.extend(toke_iter.by_ref());
                         break;
                     }
                     name_pieces.push(*toke);
                 }
             } else if *toke == "value" {
-                while let Some(toke) = toke_iter.next() {
+                for toke in toke_iter.by_ref() {
                     value_pieces.push(*toke);
                 }
             }
@@ -124,6 +209,9 @@ impl<T: Engine + Send + 'static> UCIEngine<T> {
         if name.is_empty() || value.is_empty() {
             return Ok(());
         }
+        if self.opts.get(&name) != Some(&value) {
+            self.options_changed = true;
+        }
         self.opts.insert(name, value);
         Ok(())
     }
@@ -134,9 +222,14 @@ impl<T: Engine + Send + 'static> UCIEngine<T> {
         Ok(())
     }
 
-    /// Handles the "ucinewgame" command by reinitializing the engine.
+    /// Handles the "ucinewgame" command by reinitializing the engine, which is the only time
+    /// search state such as the transposition table is thrown away.
     fn handle_ucinewgame(&mut self) -> Result<(), std::io::Error> {
         self.expect_ucinewgame = true;
+        self.options_changed = false;
+        if let Some(ref worker) = self.worker {
+            worker.reset(self.opts.clone());
+        }
         Ok(())
     }
 
@@ -144,15 +237,13 @@ impl<T: Engine + Send + 'static> UCIEngine<T> {
     /// and then applies any moves provided.
     fn handle_position(&mut self, mut tokens: &[&str]) -> Result<(), std::io::Error> {
         // "position [fen <fenstring> | startpos] [moves <move1> ... <movei>]
-        if tokens.len() == 0 {
+        if tokens.is_empty() {
             return Ok(());
         }
-        // Initialize engine if not already done.
-        if self.engine.is_none() {
-            self.engine = Some((self.initializer)(self.opts.clone()));
-        }
         if tokens[0] == "startpos" {
             // Start from the default starting position.
+            self.board = Board::default();
+            self.history = GameHistory::new();
             if tokens.len() > 1 && tokens[1] == "moves" {
                 self.apply_moves(&tokens[2..]);
             }
@@ -160,13 +251,16 @@ impl<T: Engine + Send + 'static> UCIEngine<T> {
             // The FEN string may contain spaces – it is taken until the optional "moves" token.
             let mut fen_parts = Vec::new();
             tokens = &tokens[1..];
-            while tokens.len() != 0 && tokens[0] != "moves" {
+            while !tokens.is_empty() && tokens[0] != "moves" {
                 fen_parts.push(tokens[0]);
                 tokens = &tokens[1..];
             }
             let fen = fen_parts.join(" ");
             self.board = Board::from_str(&fen).unwrap();
-            if tokens.len() != 0 && tokens[0] == "moves" {
+            // the board doesn't keep the halfmove clock, so we do.
+            let clock = fen_parts.get(4).and_then(|c| c.parse().ok()).unwrap_or(0);
+            self.history = GameHistory::with_halfmove_clock(clock);
+            if !tokens.is_empty() && tokens[0] == "moves" {
                 self.apply_moves(&tokens[1..]);
             }
         }
@@ -177,7 +271,14 @@ impl<T: Engine + Send + 'static> UCIEngine<T> {
     fn apply_moves(&mut self, moves: &[&str]) {
         for mv in moves {
             match ChessMove::from_str(mv) {
-                Ok(m) => self.board = self.board.make_move_new(m),
+                Ok(m) if self.board.legal(m) => {
+                    self.history.push(&self.board, m);
+                    self.board = self.board.make_move_new(m);
+                }
+                Ok(_) => {
+                    let _ = writeln!(self.stdout, "info string illegal uci move {mv}");
+                    break;
+                }
                 Err(e) => {
                     let _ = write!(self.stdout, "Err({e}) invalid uci move {mv}");
                     break;
@@ -186,61 +287,68 @@ impl<T: Engine + Send + 'static> UCIEngine<T> {
         }
     }
 
-    /// Handles the "go" command. This implementation ignores extra parameters (e.g. time, depth)
-    /// and simply queries the engine for its next move.
+    /// Handles the "go" command. The clock parameters are turned into soft and hard time limits by
+    /// the time manager, "movetime", "depth", "nodes" and "mate" limit the search directly and
+    /// "searchmoves" restricts the moves it may play.
     fn handle_go(&mut self, tokens: &[&str]) -> Result<(), std::io::Error> {
-        let mut time: Option<u64> = None;
+        let mut control = TimeControl::default();
         let mut depth: Option<i8> = None;
-        let mut iter = tokens.iter();
+        let mut nodes: Option<u64> = None;
+        let mut mate: Option<u32> = None;
+        let mut infinite = false;
+        let mut search_moves = Vec::new();
+        let mut iter = tokens.iter().peekable();
 
         while let Some(toke) = iter.next() {
             match *toke {
                 "wtime" => {
                     // white time remaining
-                    let _ = iter.next();
+                    control.wtime = next_value(&mut iter);
                 }
                 "btime" => {
                     // black time remaining
-                    let _ = iter.next();
+                    control.btime = next_value(&mut iter);
                 }
                 "winc" => {
                     // white increment
-                    let _ = iter.next();
+                    control.winc = next_value(&mut iter);
                 }
                 "binc" => {
                     // black increment
-                    let _ = iter.next();
+                    control.binc = next_value(&mut iter);
                 }
                 "movestogo" => {
                     // moves to go
-                    let _ = iter.next();
+                    control.movestogo = next_value(&mut iter);
                 }
                 "depth" => {
                     // depth
-                    if let Some(d) = iter.next() {
-                        if let Ok(d) = d.parse::<i8>() {
-                            depth = Some(d);
-                        }
-                    }
+                    depth = next_value(&mut iter);
                 }
                 "nodes" => {
                     // nodes
-                    let _ = iter.next();
+                    nodes = next_value(&mut iter);
                 }
                 "mate" => {
                     // mate in x
-                    let _ = iter.next();
+                    mate = next_value(&mut iter);
+                }
+                "searchmoves" => {
+                    // the moves follow until the next parameter, illegal ones are left out.
+                    while let Some(m) = iter.peek().and_then(|t| ChessMove::from_str(t).ok()) {
+                        iter.next();
+                        if self.board.legal(m) {
+                            search_moves.push(m);
+                        }
+                    }
                 }
                 "movetime" => {
                     // move time
-                    if let Some(ms_str) = iter.next() {
-                        if let Ok(ms) = ms_str.parse::<u64>() {
-                            time = Some(ms);
-                        }
-                    };
+                    control.movetime = next_value(&mut iter);
                 }
                 "infinite" => {
-                    // infinite search
+                    // infinite search, "bestmove" waits for "stop"
+                    infinite = true;
                 }
                 "ponder" => {
                     // ponder
@@ -251,35 +359,66 @@ impl<T: Engine + Send + 'static> UCIEngine<T> {
             }
         }
 
-        if let Some(engine) = self.engine.take() {
-            let mut opts = NegaMaxOptions::new();
-            if let Some(t) = time {
-                opts = opts.mtime(t);
-            }
-            if let Some(d) = depth {
-                opts = opts.depth(d);
-            }
-            self.spawn_engine_thread(engine, self.board, opts);
-        } else {
-            writeln!(self.stdout, "bestmove 0000")?;
+        // every search gets its own signal so a late "stop" can't cancel the next one.
+        self.signal = Arc::new(AtomicBool::new(false));
+        let mut opts = NegaMaxOptions::new()
+            .signal(self.signal.clone())
+            .history(self.history.clone())
+            .infinite(infinite);
+        let manager = TimeManager::new(self.move_overhead());
+        if let Some(budget) = manager.allocate(&control, self.board.side_to_move()) {
+            opts = opts.mtime(budget.hard).soft_mtime(budget.soft);
         }
-        self.stdout.flush()?;
+        if let Some(d) = depth {
+            opts = opts.depth(d);
+        }
+        if let Some(n) = nodes {
+            opts = opts.nodes(n);
+        }
+        if let Some(moves) = mate {
+            opts = opts.mate(moves);
+        }
+        if !search_moves.is_empty() {
+            opts = opts.search_moves(search_moves);
+        }
+        let board = self.board;
+        self.worker().search(board, opts);
         Ok(())
     }
 
-    fn spawn_engine_thread(&mut self, engine: T, board: Board, opts: NegaMaxOptions) {
-        spawn(move || {
-            if let Some(mv) = engine.next_move(&board, opts) {
-                println!("bestmove {}", mv);
-            } else {
-                println!("bestmove 0000");
-            }
-        });
+    fn move_overhead(&self) -> u64 {
+        return self
+            .opts
+            .get("Move Overhead")
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_MOVE_OVERHEAD);
+    }
+
+    /// Returns the engine worker, starting it with the current options on first use and
+    /// rebuilding its engine when an option changed since.
+    fn worker(&mut self) -> &EngineWorker {
+        match self.worker {
+            None => self.worker = Some(EngineWorker::new(self.initializer, self.opts.clone())),
+            Some(ref worker) if self.options_changed => worker.reset(self.opts.clone()),
+            _ => (),
+        }
+        self.options_changed = false;
+        return self.worker.as_ref().unwrap();
     }
 
-    /// Handles the "stop" command.
-    /// In this synchronous implementation (where the search is blocking), this is a no-op.
+    /// Handles the "stop" command by signalling the running search to return the best move
+    /// of its last completed iteration. The worker prints "bestmove" and is then ready for the next "go".
     fn handle_stop(&mut self) -> Result<(), std::io::Error> {
+        self.signal.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Handles the "quit" command by stopping any search and joining the engine worker.
+    fn handle_quit(&mut self) -> Result<(), std::io::Error> {
+        self.handle_stop()?;
+        if let Some(mut worker) = self.worker.take() {
+            worker.join();
+        }
         Ok(())
     }
 
@@ -295,6 +434,11 @@ impl<T: Engine + Send + 'static> UCIEngine<T> {
     }
 }
 
+/// Parses the value following a "go" parameter, e.g. the "1000" in "movetime 1000".
+fn next_value<'a, V: FromStr>(iter: &mut impl Iterator<Item = &'a &'a str>) -> Option<V> {
+    return iter.next().and_then(|v| v.parse::<V>().ok());
+}
+
 #[derive(Debug)]
 pub struct UCITestEngine {
     outdir: String,
@@ -333,7 +477,7 @@ impl UCITestEngine {
         self.setup_engine(&mut white)?;
         self.setup_engine(&mut black)?;
         let mut game = Game::new();
-        let mut encoder = PgnEncoder::new(game.current_position().clone(), None);
+        let mut encoder = PgnEncoder::new(game.current_position(), None);
         let mut eng1_wins = 0;
         let mut eng2_wins = 0;
         let mut white_wins = 0;
@@ -387,15 +531,19 @@ impl UCITestEngine {
                 draws += 1;
             }
 
+            // a draw we could claim but the game didn't detect by itself is still a draw.
+            encoder.set_outcome(
+                game.result()
+                    .unwrap_or(chess::GameResult::DrawDeclared)
+                    .into(),
+            );
             let pgn = encoder.encode();
             let filename = format!("{}/game_{}.pgn", self.outdir, game_num);
             Self::write_pgn_evidence(filename, pgn)?;
 
-            let tmp = black;
-            black = white;
-            white = tmp;
+            std::mem::swap(&mut black, &mut white);
             game = Game::new();
-            encoder = PgnEncoder::new(game.current_position().clone(), None);
+            encoder = PgnEncoder::new(game.current_position(), None);
             println!("Game {} complete", game_num);
             println!("Engine 1 wins: {}", eng1_wins);
             println!("Engine 2 wins: {}", eng2_wins);
@@ -466,7 +614,7 @@ impl UCITestEngine {
             }
         }
         print!("engout -> {}", line);
-        let parts: Vec<&str> = line.trim().split_whitespace().collect();
+        let parts: Vec<&str> = line.split_whitespace().collect();
         if parts.len() < 2 {
             return Err(std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
@@ -522,3 +670,46 @@ impl Default for UCITestEngine {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chess::MoveGen;
+    use std::sync::Mutex;
+
+    // the MultiPV value of every engine that searched.
+    static SEARCHED_WITH: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    struct OptionsEngine {
+        multi_pv: String,
+    }
+
+    impl Engine for OptionsEngine {
+        fn next_move(&self, board: &Board, _opts: NegaMaxOptions) -> Option<ChessMove> {
+            SEARCHED_WITH.lock().unwrap().push(self.multi_pv.clone());
+            return MoveGen::new_legal(board).next();
+        }
+    }
+
+    fn options_engine(opts: UCIEngineOptions) -> OptionsEngine {
+        let multi_pv = opts.get("MultiPV").cloned().unwrap_or_default();
+        return OptionsEngine { multi_pv };
+    }
+
+    #[test]
+    fn options_set_after_a_search_are_used() {
+        let mut uci = UCIEngine::new(options_engine);
+        uci.handle_setoption(&["name", "MultiPV", "value", "2"])
+            .unwrap();
+        uci.handle_go(&["depth", "1"]).unwrap();
+        uci.handle_setoption(&["name", "MultiPV", "value", "3"])
+            .unwrap();
+        uci.handle_go(&["depth", "1"]).unwrap();
+        // setting the same value again keeps the engine.
+        uci.handle_setoption(&["name", "MultiPV", "value", "3"])
+            .unwrap();
+        uci.handle_go(&["depth", "1"]).unwrap();
+        uci.handle_quit().unwrap();
+        assert_eq!(*SEARCHED_WITH.lock().unwrap(), vec!["2", "3", "3"]);
+    }
+}
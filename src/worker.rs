@@ -0,0 +1,77 @@
+use crate::engine::Engine;
+use crate::evaluation::NegaMaxOptions;
+use crate::uci::UCIEngineOptions;
+use chess::Board;
+use std::sync::mpsc::{channel, Sender};
+use std::thread::{spawn, JoinHandle};
+
+enum WorkerCommand {
+    Search(Board, NegaMaxOptions),
+    Reset(UCIEngineOptions),
+    Quit,
+}
+
+/// Owns an engine on a dedicated thread for the whole uci session. Searches run one after
+/// another on that thread, so state such as the transposition table carries over from one
+/// move to the next until the engine is explicitly reset.
+pub struct EngineWorker {
+    sender: Sender<WorkerCommand>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl EngineWorker {
+    pub fn new<T: Engine + Send + 'static>(
+        initializer: fn(opts: UCIEngineOptions) -> T,
+        opts: UCIEngineOptions,
+    ) -> Self {
+        let (sender, receiver) = channel();
+        let handle = spawn(move || {
+            let mut engine = initializer(opts);
+            for command in receiver {
+                match command {
+                    WorkerCommand::Search(board, opts) => {
+                        let best_move = engine.next_move(&board, opts.clone());
+                        opts.wait_for_stop();
+                        if let Some(mv) = best_move {
+                            println!("bestmove {}", mv);
+                        } else {
+                            println!("bestmove 0000");
+                        }
+                    }
+                    WorkerCommand::Reset(opts) => engine = initializer(opts),
+                    WorkerCommand::Quit => break,
+                }
+            }
+        });
+        return Self {
+            sender,
+            handle: Some(handle),
+        };
+    }
+
+    /// Queue a search, the worker prints "bestmove" when it is done (or, for an infinite
+    /// search, once it is stopped).
+    pub fn search(&self, board: Board, opts: NegaMaxOptions) {
+        let _ = self.sender.send(WorkerCommand::Search(board, opts));
+    }
+
+    /// Replace the engine with a fresh one built from `opts` (e.g., on "ucinewgame").
+    pub fn reset(&self, opts: UCIEngineOptions) {
+        let _ = self.sender.send(WorkerCommand::Reset(opts));
+    }
+
+    /// Waits for queued work to finish and shuts the thread down. A running search
+    /// should be signalled to stop first, otherwise this blocks until it completes.
+    pub fn join(&mut self) {
+        let _ = self.sender.send(WorkerCommand::Quit);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for EngineWorker {
+    fn drop(&mut self) {
+        self.join();
+    }
+}